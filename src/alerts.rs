@@ -0,0 +1,86 @@
+//! Weather alerts in full, for the `alerts` subcommand.
+
+use clap::ArgMatches;
+
+use crate::forecast::Forecast;
+use crate::{format_alert, get_forecasts, Config, Result};
+
+pub fn run(config: &Config, matches: &ArgMatches<'_>) -> Result<()> {
+    let forecasts = get_forecasts(config, matches)?;
+    let several = forecasts.len() > 1;
+
+    let sections: Vec<String> = forecasts
+        .iter()
+        .map(|(location, weather, _)| {
+            let alerts = format_alerts(weather);
+            if several {
+                let name = weather.place.as_ref().unwrap_or(location);
+                format!("{}\n{}", name, alerts)
+            } else {
+                alerts
+            }
+        })
+        .collect();
+
+    println!("{}", sections.join("\n\n"));
+    Ok(())
+}
+
+/// Every alert that hasn't expired, or a note that there aren't any.
+fn format_alerts(weather: &Forecast) -> String {
+    let alerts = weather.active_alerts();
+    if alerts.is_empty() {
+        return "no weather alerts".to_string();
+    }
+
+    alerts
+        .into_iter()
+        .map(|alert| format_alert(alert, weather.utc_offset, true))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forecast::{Alert, DataPoint, Severity};
+
+    #[test]
+    fn active_alerts_only() {
+        let mut weather = Forecast {
+            current: DataPoint {
+                time: 1_559_408_400,
+                ..DataPoint::default()
+            },
+            ..Forecast::default()
+        };
+        assert_eq!("no weather alerts", format_alerts(&weather));
+
+        weather.alerts = vec![
+            Alert {
+                title: "Frost Advisory".to_string(),
+                expires: Some(1_559_400_000),
+                ..Alert::default()
+            },
+            Alert {
+                title: "Severe Thunderstorm Warning".to_string(),
+                description: "Large hail and damaging winds.\n".to_string(),
+                time: 1_559_404_800,
+                expires: Some(1_559_430_000),
+                severity: Some(Severity::Warning),
+                uri: Some("https://example.com/alert".to_string()),
+                regions: vec!["Winnipeg".to_string(), "Selkirk".to_string()],
+            },
+        ];
+
+        let alerts = format_alerts(&weather);
+        assert!(!alerts.contains("Frost"));
+        let lines: Vec<_> = alerts.lines().collect();
+        assert_eq!("\u{26a0} Severe Thunderstorm Warning (warning)", lines[0]);
+        assert!(lines[1].starts_with("issued "));
+        assert!(lines[1].contains(", until "));
+        assert_eq!("for Winnipeg, Selkirk", lines[2]);
+        assert_eq!("Large hail and damaging winds.", lines[4]);
+        assert_eq!("https://example.com/alert", lines[5]);
+    }
+}
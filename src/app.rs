@@ -2,10 +2,13 @@ use chrono::{Local, Timelike};
 use clap::{App, Arg, Shell, SubCommand};
 use lazy_static::lazy_static;
 
+use crate::forecast::Metric;
+
 lazy_static! {
     // This is the same as $(date -Is), that is, "%Y-%m-%dT%H:%M:%S%:z".
     // Nanoseconds are stripped away because the Dark Sky API doesn't accept them.
     static ref NOW: String = Local::now().with_nanosecond(0).expect("couldn't create local time with 0 nanoseconds").to_rfc3339();
+    static ref METRICS: Vec<&'static str> = Metric::ALL.iter().map(|m| m.name()).collect();
 }
 
 pub fn build_cli() -> App<'static, 'static> {
@@ -20,18 +23,92 @@ pub fn build_cli() -> App<'static, 'static> {
         )
         .arg(Arg::with_name("latitude").env("WEATHER_LAT"))
         .arg(Arg::with_name("longitude").env("WEATHER_LON"))
+        .arg(
+            Arg::with_name("place")
+                .short("p")
+                .long("place")
+                .help("Look up the weather by place name, e.g. \"Winnipeg, MB\".")
+                .long_help(
+                    "Look up the weather by place name, e.g. \"Winnipeg, MB\". Coordinates \
+                     given on the command line take precedence over this.",
+                )
+                .takes_value(true)
+                .conflicts_with_all(&["debug", "local"]),
+        )
+        .arg(
+            Arg::with_name("location")
+                .long("location")
+                .help("Use a location from the [locations] section of the config file.")
+                .long_help(
+                    "Use a location from the [locations] section of the config file, instead \
+                     of `default_location`. Give this more than once to show the weather for \
+                     each location. Coordinates given on the command line take precedence \
+                     over this.",
+                )
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .conflicts_with_all(&["place", "debug", "local"]),
+        )
         .arg(
             Arg::with_name("i3")
                 .short("3")
                 .long("i3blocks")
                 .help("format output for i3blocks pango."),
         )
+        .arg(
+            Arg::with_name("waybar")
+                .long("waybar")
+                .help("format output as JSON for a Waybar custom module.")
+                .conflicts_with_all(&["i3", "long"]),
+        )
+        .arg(
+            Arg::with_name("polybar")
+                .long("polybar")
+                .alias("lemonbar")
+                .help("format output for Polybar or lemonbar.")
+                .conflicts_with_all(&["i3", "waybar"]),
+        )
+        .arg(
+            Arg::with_name("tmux")
+                .long("tmux")
+                .help("format output for tmux's status line.")
+                .conflicts_with_all(&["i3", "waybar", "polybar"]),
+        )
         .arg(
             Arg::with_name("long")
                 .short("l")
                 .long("long")
                 .help("show detailed output"),
         )
+        .arg(
+            Arg::with_name("graph")
+                .short("g")
+                .long("graph")
+                .help("What to graph from the hourly forecast.")
+                .long_help(
+                    "What to graph from the hourly forecast, instead of `graphs` in the config \
+                     file. Give this more than once for several graphs.",
+                )
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .possible_values(&METRICS),
+        )
+        .arg(
+            Arg::with_name("daily_graph")
+                .long("daily-graph")
+                .help("What to graph from the daily forecast in the --long output.")
+                .long_help(
+                    "What to graph from the daily forecast in the --long output, instead of \
+                     `daily_graphs` in the config file. Give this more than once for several \
+                     graphs.",
+                )
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .possible_values(&METRICS),
+        )
         .arg(
             Arg::with_name("debug")
                 .short("D")
@@ -45,10 +122,19 @@ pub fn build_cli() -> App<'static, 'static> {
                 .conflicts_with("historical"),
         )
         .arg(
-            Arg::with_name("json")
-                .long("json")
+            Arg::with_name("raw")
+                .long("raw")
+                .alias("json")
                 .help("just show the raw json response")
-                .conflicts_with_all(&["i3", "long", "local"]),
+                .conflicts_with_all(&["i3", "waybar", "polybar", "tmux", "long", "local"]),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .help("how to show the forecast; json is a stable, versioned schema")
+                .takes_value(true)
+                .possible_values(&["text", "json"])
+                .conflicts_with_all(&["i3", "waybar", "polybar", "tmux", "long", "raw"]),
         )
         .arg(
             Arg::with_name("local")
@@ -77,6 +163,119 @@ pub fn build_cli() -> App<'static, 'static> {
                 .help("Extend the hourly forecast from 48 hours to 168 hours.")
                 .conflicts_with_all(&["debug", "historical"]),
         )
+        .arg(
+            Arg::with_name("refresh")
+                .short("r")
+                .long("refresh")
+                .help("Ignore cached forecasts and ask the provider for a new one."),
+        )
+        .subcommand(
+            SubCommand::with_name("alerts")
+                .about("Show any weather alerts in full.")
+                .long_about(
+                    "Show any weather alerts in full, leaving out the ones that have expired. \
+                     Location options go before `alerts`.",
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("bar")
+                .about("Run as a status line for i3bar or swaybar.")
+                .long_about(
+                    "Run as a status line for i3bar or swaybar, using the i3bar protocol. Use \
+                     this as the `status_command` in i3's config. Left click to show more \
+                     details.",
+                )
+                .arg(
+                    Arg::with_name("interval")
+                        .short("i")
+                        .long("interval")
+                        .help("How often to fetch a new forecast, in seconds.")
+                        .takes_value(true)
+                        .default_value("600"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("hourly")
+                .about("Show the hourly forecast as a table.")
+                .long_about(
+                    "Show the hourly forecast as a table, with the current hour marked. Location \
+                     options go before `hourly`.",
+                )
+                .arg(
+                    Arg::with_name("hours")
+                        .short("n")
+                        .long("hours")
+                        .help("How many hours to show. [default: 24, or 168 with --extend]")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("extend_hourly")
+                        .short("e")
+                        .long("extend")
+                        .help("Extend the hourly forecast from 48 hours to 168 hours."),
+                )
+                .arg(chart_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("daily")
+                .about("Show the daily forecast as a table.")
+                .long_about(
+                    "Show the daily forecast as a table, with today marked. Location options go \
+                     before `daily`.",
+                )
+                .arg(chart_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("chart")
+                .about("Draw the forecast as an SVG chart.")
+                .long_about(
+                    "Draw the hourly forecast as an SVG chart: the temperature and what it feels \
+                     like, the chance of precipitation as bars, and the pressure on its own axis, \
+                     with the nights shaded and now marked. The colours are set in the [svg] \
+                     section of the config file. Location options go before `chart`.",
+                )
+                .arg(
+                    Arg::with_name("svg")
+                        .long("svg")
+                        .value_name("FILE")
+                        .help("Where to save the chart, or - to print it.")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("daily")
+                        .short("d")
+                        .long("daily")
+                        .help("Chart the daily forecast, with the highs and lows, instead."),
+                )
+                .arg(
+                    Arg::with_name("width")
+                        .long("width")
+                        .help("How wide the chart is, in pixels.")
+                        .default_value("800"),
+                )
+                .arg(
+                    Arg::with_name("height")
+                        .long("height")
+                        .help("How tall the chart is for each location, in pixels.")
+                        .default_value("300"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("notify")
+                .about("Send desktop notifications about what's changed since last time.")
+                .long_about(
+                    "Send desktop notifications about new alerts, precipitation starting soon, \
+                     and the temperature crossing the thresholds in the [notify] section of the \
+                     config file. Run this every so often; each is only sent once. Location \
+                     options go before `notify`.",
+                )
+                .arg(
+                    Arg::with_name("print")
+                        .long("print")
+                        .help("Print the notifications instead of sending them."),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("completions").arg(
                 Arg::with_name("shell")
@@ -85,3 +284,19 @@ pub fn build_cli() -> App<'static, 'static> {
             ),
         )
 }
+
+/// Draw charts above the `hourly` and `daily` tables.
+fn chart_arg() -> Arg<'static, 'static> {
+    Arg::with_name("chart")
+        .short("c")
+        .long("chart")
+        .help("Chart this above the table.")
+        .long_help(
+            "Chart this above the table, as a braille line chart `chart_height` rows tall (4 by \
+             default). Give this more than once for several charts.",
+        )
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .possible_values(&METRICS)
+}
@@ -0,0 +1,229 @@
+//! A long-running status line for i3bar and swaybar, speaking the i3bar protocol on stdout.
+//!
+//! Unlike i3blocks, which runs this program again for each update, the forecast is kept
+//! between updates: the graph highlight moves along with the hour, and it's only fetched again
+//! every `--interval` seconds.
+
+use chrono::{Local, Timelike};
+use clap::ArgMatches;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::io::{self, BufRead};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::forecast::Forecast;
+use crate::{format_weather, get_forecasts, Config, Error, Forecasts, Mode, Result};
+
+/// The name of every block, so that clicks on them can be told apart from other programs'.
+const NAME: &str = env!("CARGO_PKG_NAME");
+
+/// A click on a block, sent by i3bar on stdin.
+#[derive(Debug, Deserialize, PartialEq)]
+struct Click {
+    name: Option<String>,
+    instance: Option<String>,
+    button: u32,
+}
+
+pub fn run(config: &Config, matches: &ArgMatches<'_>, bar_matches: &ArgMatches<'_>) -> Result<()> {
+    let interval = Duration::from_secs(value_t!(bar_matches.value_of("interval"), u64)?);
+
+    let (clicks, received) = mpsc::channel();
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        for line in stdin.lock().lines() {
+            let line = match line {
+                Ok(l) => l,
+                Err(_) => break,
+            };
+            if let Some(click) = parse_click(&line) {
+                if clicks.send(click).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    println!("{}", json!({ "version": 1, "click_events": true }));
+    println!("[");
+
+    let mut forecasts: Result<Forecasts> = Ok(Vec::new());
+    let mut fetched: Option<Instant> = None;
+    let mut expanded = HashSet::new();
+
+    loop {
+        let due = match fetched {
+            Some(f) => f.elapsed() >= interval,
+            None => true,
+        };
+        if due {
+            let latest = get_forecasts(config, matches);
+            if let Err(ref e) = latest {
+                warn!("couldn't update the forecast: {}", e);
+            }
+            // Keep showing the last forecast rather than an error, if there is one.
+            if latest.is_ok() || forecasts.as_ref().map_or(true, Vec::is_empty) {
+                forecasts = latest;
+            }
+            fetched = Some(Instant::now());
+        }
+
+        let blocks = match forecasts {
+            Ok(ref forecasts) => forecasts
+                .iter()
+                .map(|(instance, weather, stale_since)| {
+                    block(
+                        config,
+                        instance,
+                        weather,
+                        expanded.contains(instance),
+                        *stale_since,
+                    )
+                })
+                .collect(),
+            Err(ref e) => vec![error_block(None, e)],
+        };
+        println!("{},", Value::Array(blocks));
+
+        // Wake up for the next fetch, or at the top of the hour to move the highlight along.
+        let since_fetch = fetched.map_or(Duration::from_secs(0), |f| f.elapsed());
+        let wait = interval
+            .checked_sub(since_fetch)
+            .unwrap_or_default()
+            .min(until_next_hour());
+
+        match received.recv_timeout(wait) {
+            Ok(click) => {
+                if click.name.as_deref() == Some(NAME) && click.button == 1 {
+                    let instance = click.instance.unwrap_or_default();
+                    if !expanded.remove(&instance) {
+                        expanded.insert(instance);
+                    }
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            // Nothing more to read from stdin, so just wait.
+            Err(RecvTimeoutError::Disconnected) => thread::sleep(wait),
+        }
+    }
+}
+
+/// One block of the status line, with the `--long` details on one line after the usual
+/// output when `expanded`. If the weather can't be formatted, the block shows why instead, so
+/// that the bar keeps running.
+fn block(
+    config: &Config,
+    instance: &str,
+    weather: &Forecast,
+    expanded: bool,
+    stale_since: Option<i64>,
+) -> Value {
+    let full_text = format_weather(
+        config,
+        weather.clone(),
+        Mode::I3blocks,
+        expanded,
+        Local::now(),
+        stale_since,
+    );
+
+    match full_text {
+        Ok(full_text) => json!({
+            "name": NAME,
+            "instance": instance,
+            "full_text": full_text.replace('\n', " "),
+            "markup": "pango",
+        }),
+        Err(e) => error_block(Some(instance), &e),
+    }
+}
+
+/// A block showing an error, marked urgent so that it stands out.
+fn error_block(instance: Option<&str>, error: &Error) -> Value {
+    let mut block = json!({
+        "name": NAME,
+        "full_text": error.to_string(),
+        "urgent": true,
+    });
+    if let Some(instance) = instance {
+        block["instance"] = json!(instance);
+    }
+    block
+}
+
+/// Read a click event. After the first, each is preceded by a comma, since they're part of an
+/// endless JSON array.
+fn parse_click(line: &str) -> Option<Click> {
+    let line = line.trim().trim_start_matches(&['[', ','][..]);
+    if line.is_empty() {
+        return None;
+    }
+    match serde_json::from_str(line) {
+        Ok(click) => Some(click),
+        Err(e) => {
+            warn!("ignoring click event {:?}: {}", line, e);
+            None
+        }
+    }
+}
+
+fn until_next_hour() -> Duration {
+    let now = Local::now();
+    let seconds = 60 * 60 - u64::from(now.minute() * 60 + now.second());
+    Duration::from_secs(seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::darksky_forecast;
+
+    #[test]
+    fn click_events() {
+        assert_eq!(None, parse_click("["));
+        assert_eq!(
+            Some(Click {
+                name: Some("weather".to_string()),
+                instance: Some("home".to_string()),
+                button: 1,
+            }),
+            parse_click(r#"{"name":"weather","instance":"home","button":1,"x":1,"y":2}"#)
+        );
+        assert_eq!(
+            Some(3),
+            parse_click(r#",{"name":"weather","button":3}"#).map(|c| c.button)
+        );
+        assert_eq!(None, parse_click("not json"));
+    }
+
+    #[test]
+    fn expanded_block() {
+        let weather = darksky_forecast();
+        let config = Config::default();
+
+        let collapsed = block(&config, "home", &weather, false, None);
+        assert_eq!(json!("weather"), collapsed["name"]);
+        assert_eq!(json!("home"), collapsed["instance"]);
+        assert_eq!(json!("pango"), collapsed["markup"]);
+
+        let expanded = block(&config, "home", &weather, true, None);
+        let collapsed = collapsed["full_text"].as_str().unwrap();
+        let expanded = expanded["full_text"].as_str().unwrap();
+        assert!(expanded.starts_with(collapsed));
+        assert!(expanded.contains("hourly pressure forecast: "));
+        assert!(!expanded.contains('\n'));
+    }
+
+    #[test]
+    fn error_in_block() {
+        let mut weather = darksky_forecast();
+        // Out of the range the moon icons cover.
+        weather.daily.data[0].moon_phase = Some(2.);
+
+        let block = block(&Config::default(), "home", &weather, false, None);
+        assert_eq!(json!("home"), block["instance"]);
+        assert_eq!(json!(true), block["urgent"]);
+    }
+}
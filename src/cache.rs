@@ -0,0 +1,396 @@
+//! Files kept between runs, under `$XDG_CACHE_HOME/weather/`.
+
+use chrono::{Local, Utc};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use crate::provider::{self, Provider, Request};
+use crate::{Error, Result};
+
+/// How long a cached forecast is used before asking the provider again, in seconds.
+pub const DEFAULT_TTL: u64 = 10 * 60;
+
+/// A provider's response and when it was fetched.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Entry {
+    pub fetched: i64,
+    pub response: Value,
+    /// Set when the provider couldn't be reached, and this is the last response it gave.
+    #[serde(skip)]
+    pub stale: bool,
+}
+
+/// The default cache directory, if the platform has one.
+pub fn dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|d| d.join(env!("CARGO_PKG_NAME")))
+}
+
+/// Read `name` from the cache directory. Missing or unreadable entries are treated as absent.
+pub fn read<T: DeserializeOwned>(dir: &Path, name: &str) -> Option<T> {
+    let path = dir.join(name);
+    let f = File::open(&path).ok()?;
+    match serde_json::from_reader(f) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            warn!("ignoring unreadable cache file {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+pub fn write<T: Serialize>(dir: &Path, name: &str, value: &T) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    let f = File::create(dir.join(name))?;
+    serde_json::to_writer(f, value)?;
+    Ok(())
+}
+
+/// The name of the cache file for a forecast request to `provider` at `url`.
+///
+/// Every provider is asked for Dark Sky's `ca` units, but they're part of the name anyway so
+/// that a future change to units doesn't pick up old responses.
+pub fn forecast_name(provider: &str, url: &str, request: &Request) -> Result<String> {
+    let mut name = format!(
+        "forecast-{}-{}-{:.4},{:.4}-ca",
+        provider,
+        short_hash(url),
+        request.lat,
+        request.lon
+    );
+    if request.extend_hourly {
+        name.push_str("-extended");
+    }
+    if let Some(ref time) = request.time {
+        name.push_str(&format!("-at-{}", provider::parse_time(time)?.timestamp()));
+    }
+    name.push_str(".json");
+    Ok(name)
+}
+
+/// A short name for `text` that stays the same between runs and versions, unlike the standard
+/// library's hashers. This is 32-bit FNV-1a.
+pub fn short_hash(text: &str) -> String {
+    let hash = text.bytes().fold(0x811c_9dc5_u32, |hash, b| {
+        (hash ^ u32::from(b)).wrapping_mul(0x0100_0193)
+    });
+    format!("{:08x}", hash)
+}
+
+/// Fetch a forecast, reusing the cached response if it's less than `ttl` seconds old.
+///
+/// Historical forecasts for days before today are reused no matter how old they are, since past
+/// weather doesn't change. Pass `refresh` to skip the cache and always ask the provider.
+///
+/// If the provider can't be reached, or has trouble of its own, the last response is used no
+/// matter how old it is, and marked as stale. It's only an error if there isn't one. Requests
+/// it refuses, e.g. for a bad token, are always an error, so they aren't hidden.
+pub fn fetch(
+    dir: &Path,
+    ttl: u64,
+    refresh: bool,
+    provider: &dyn Provider,
+    request: &Request,
+) -> Result<Entry> {
+    let name = forecast_name(provider.name(), provider.url(), request)?;
+    let now = Utc::now().timestamp();
+    let past = is_past(request)?;
+
+    if !refresh {
+        if let Some(entry) = read::<Entry>(dir, &name) {
+            if past || now - entry.fetched < ttl as i64 {
+                info!("using cached forecast from {}", entry.fetched);
+                return Ok(entry);
+            }
+        }
+    }
+
+    let response = match provider.fetch(request) {
+        Ok(response) => response,
+        Err(Error::Reqwest(e)) => {
+            if !is_unavailable(&e) {
+                return Err(Error::Reqwest(e));
+            }
+            return match read::<Entry>(dir, &name) {
+                Some(entry) => {
+                    warn!("{}; using the forecast from {}", e, entry.fetched);
+                    Ok(Entry {
+                        stale: true,
+                        ..entry
+                    })
+                }
+                None => Err(Error::Reqwest(e)),
+            };
+        }
+        Err(e) => return Err(e),
+    };
+
+    let entry = Entry {
+        fetched: now,
+        response,
+        stale: false,
+    };
+
+    if let Err(e) = write(dir, &name, &entry) {
+        warn!("couldn't cache forecast: {}", e);
+    }
+
+    Ok(entry)
+}
+
+/// Whether the provider couldn't be reached, or had trouble of its own, rather than refusing
+/// the request.
+fn is_unavailable(error: &reqwest::Error) -> bool {
+    error.is_http() || error.is_timeout() || error.is_server_error()
+}
+
+/// Whether `request` is for a time before the last midnight, whose weather won't change.
+fn is_past(request: &Request) -> Result<bool> {
+    match request.time {
+        Some(ref time) => {
+            let midnight = Local::today().and_hms(0, 0, 0);
+            Ok(provider::parse_time(time)?.timestamp() < midnight.timestamp())
+        }
+        None => Ok(false),
+    }
+}
+
+#[cfg(test)]
+pub fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "{}-test-{}-{}",
+        env!("CARGO_PKG_NAME"),
+        name,
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forecast::Forecast;
+    use crate::test_server::{Route, TestServer};
+    use std::cell::Cell;
+
+    /// Counts how many times it was asked for a forecast.
+    #[derive(Default)]
+    struct CountingProvider {
+        fetches: Cell<u32>,
+    }
+
+    impl Provider for CountingProvider {
+        fn name(&self) -> &'static str {
+            "counting"
+        }
+
+        fn url(&self) -> &str {
+            "http://counting.example"
+        }
+
+        fn fetch(&self, _request: &Request) -> Result<Value> {
+            self.fetches.set(self.fetches.get() + 1);
+            Ok(Value::from(self.fetches.get()))
+        }
+
+        fn parse(&self, _raw: Value) -> Result<Forecast> {
+            Ok(Forecast::default())
+        }
+    }
+
+    /// Can't reach its server.
+    struct OfflineProvider;
+
+    impl Provider for OfflineProvider {
+        fn name(&self) -> &'static str {
+            "counting"
+        }
+
+        fn url(&self) -> &str {
+            "http://counting.example"
+        }
+
+        fn fetch(&self, _request: &Request) -> Result<Value> {
+            // Nothing listens on port 1.
+            reqwest::get("http://127.0.0.1:1")?;
+            unreachable!()
+        }
+
+        fn parse(&self, _raw: Value) -> Result<Forecast> {
+            Ok(Forecast::default())
+        }
+    }
+
+    /// Answered by a test server.
+    struct ServerProvider {
+        url: String,
+    }
+
+    impl Provider for ServerProvider {
+        fn name(&self) -> &'static str {
+            "counting"
+        }
+
+        fn url(&self) -> &str {
+            "http://counting.example"
+        }
+
+        fn fetch(&self, _request: &Request) -> Result<Value> {
+            Ok(reqwest::get(&self.url)?.error_for_status()?.json()?)
+        }
+
+        fn parse(&self, _raw: Value) -> Result<Forecast> {
+            Ok(Forecast::default())
+        }
+    }
+
+    fn request() -> Request {
+        Request {
+            lat: 49.9,
+            lon: -97.14,
+            ..Request::default()
+        }
+    }
+
+    #[test]
+    fn write_then_read() {
+        let dir = test_dir("cache-write-then-read");
+        assert_eq!(None, read::<Vec<u32>>(&dir, "numbers.json"));
+
+        write(&dir, "numbers.json", &vec![1, 2, 3]).unwrap();
+        assert_eq!(Some(vec![1, 2, 3]), read(&dir, "numbers.json"));
+
+        fs::write(dir.join("numbers.json"), "not json").unwrap();
+        assert_eq!(None, read::<Vec<u32>>(&dir, "numbers.json"));
+    }
+
+    #[test]
+    fn forecast_names() {
+        let url = "https://api.darksky.net";
+        assert_eq!(
+            "forecast-counting-8d9f7cac-49.9000,-97.1400-ca.json",
+            forecast_name("counting", url, &request()).unwrap()
+        );
+        assert_ne!(
+            forecast_name("counting", url, &request()).unwrap(),
+            forecast_name("counting", "https://api.pirateweather.net", &request()).unwrap()
+        );
+        let request = Request {
+            extend_hourly: true,
+            time: Some("2019-06-01T12:00:00-05:00".to_string()),
+            ..request()
+        };
+        assert_eq!(
+            "forecast-counting-8d9f7cac-49.9000,-97.1400-ca-extended-at-1559408400.json",
+            forecast_name("counting", url, &request).unwrap()
+        );
+    }
+
+    #[test]
+    fn fetch_reuses_fresh_responses() {
+        let dir = test_dir("cache-fresh");
+        let provider = CountingProvider::default();
+
+        let entry = fetch(&dir, 60, false, &provider, &request()).unwrap();
+        assert_eq!(Value::from(1), entry.response);
+        assert!(!entry.stale);
+        let entry = fetch(&dir, 60, false, &provider, &request()).unwrap();
+        assert_eq!(Value::from(1), entry.response);
+        let entry = fetch(&dir, 60, true, &provider, &request()).unwrap();
+        assert_eq!(Value::from(2), entry.response);
+
+        let other = Request {
+            extend_hourly: true,
+            ..request()
+        };
+        let entry = fetch(&dir, 60, false, &provider, &other).unwrap();
+        assert_eq!(Value::from(3), entry.response);
+    }
+
+    #[test]
+    fn fetch_expires_responses() {
+        let dir = test_dir("cache-expired");
+        let provider = CountingProvider::default();
+
+        fetch(&dir, 0, false, &provider, &request()).unwrap();
+        fetch(&dir, 0, false, &provider, &request()).unwrap();
+        assert_eq!(2, provider.fetches.get());
+    }
+
+    #[test]
+    fn fetch_keeps_historical_responses() {
+        let dir = test_dir("cache-historical");
+        let provider = CountingProvider::default();
+        let request = Request {
+            time: Some("1559408400".to_string()),
+            ..request()
+        };
+
+        fetch(&dir, 0, false, &provider, &request).unwrap();
+        fetch(&dir, 0, false, &provider, &request).unwrap();
+        assert_eq!(1, provider.fetches.get());
+    }
+
+    #[test]
+    fn fetch_expires_future_responses() {
+        let dir = test_dir("cache-future");
+        let provider = CountingProvider::default();
+        let tomorrow = Utc::now().timestamp() + 24 * 60 * 60;
+        let request = Request {
+            time: Some(tomorrow.to_string()),
+            ..request()
+        };
+
+        fetch(&dir, 0, false, &provider, &request).unwrap();
+        fetch(&dir, 0, false, &provider, &request).unwrap();
+        assert_eq!(2, provider.fetches.get());
+
+        fetch(&dir, 60, false, &provider, &request).unwrap();
+        assert_eq!(2, provider.fetches.get());
+    }
+
+    #[test]
+    fn fetch_falls_back_when_offline() {
+        let dir = test_dir("cache-offline");
+
+        match fetch(&dir, 0, false, &OfflineProvider, &request()) {
+            Err(Error::Reqwest(_)) => {}
+            r => panic!("expected a network error, got {:?}", r),
+        }
+
+        fetch(&dir, 0, false, &CountingProvider::default(), &request()).unwrap();
+        let entry = fetch(&dir, 0, true, &OfflineProvider, &request()).unwrap();
+        assert!(entry.stale);
+        assert_eq!(Value::from(1), entry.response);
+    }
+
+    #[test]
+    fn fetch_only_falls_back_when_unavailable() {
+        let dir = test_dir("cache-unavailable");
+        let server = TestServer::new(vec![
+            Route {
+                status: 503,
+                ..Route::new("/unavailable", "")
+            },
+            Route {
+                status: 403,
+                ..Route::new("/forbidden", "")
+            },
+        ]);
+        let provider = |path: &str| ServerProvider {
+            url: format!("{}{}", server.url, path),
+        };
+        fetch(&dir, 0, false, &CountingProvider::default(), &request()).unwrap();
+
+        let entry = fetch(&dir, 0, true, &provider("/unavailable"), &request()).unwrap();
+        assert!(entry.stale);
+
+        match fetch(&dir, 0, true, &provider("/forbidden"), &request()) {
+            Err(Error::Reqwest(ref e)) if e.is_client_error() => {}
+            r => panic!("expected the request to be refused, got {:?}", r),
+        }
+    }
+}
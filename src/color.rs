@@ -17,6 +17,45 @@ impl Color {
         )
     }
 
+    /// The color as `#rrggbb`, without alpha.
+    pub fn rgb_hex(self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.red, self.green, self.blue)
+    }
+
+    /// The closest colour in the xterm 256 colour palette, either from its 6×6×6 colour cube
+    /// or its greyscale ramp.
+    pub fn ansi256(self) -> u8 {
+        const LEVELS: [u8; 6] = [0, 0x5f, 0x87, 0xaf, 0xd7, 0xff];
+
+        let nearest_level = |c: u8| -> usize {
+            (0..LEVELS.len())
+                .min_by_key(|&i| (i32::from(LEVELS[i]) - i32::from(c)).abs())
+                .unwrap_or_default()
+        };
+        let distance = |(r, g, b): (u8, u8, u8)| -> i32 {
+            let d = |a: u8, b: u8| (i32::from(a) - i32::from(b)).pow(2);
+            d(r, self.red) + d(g, self.green) + d(b, self.blue)
+        };
+
+        let (r, g, b) = (
+            nearest_level(self.red),
+            nearest_level(self.green),
+            nearest_level(self.blue),
+        );
+        let cube = (LEVELS[r], LEVELS[g], LEVELS[b]);
+
+        // The greyscale ramp goes from 8 to 238 in steps of 10.
+        let average = (u32::from(self.red) + u32::from(self.green) + u32::from(self.blue)) / 3;
+        let grey_index = (average.saturating_sub(3) / 10).min(23) as u8;
+        let grey_level = 8 + grey_index * 10;
+
+        if distance((grey_level, grey_level, grey_level)) < distance(cube) {
+            232 + grey_index
+        } else {
+            16 + 36 * r as u8 + 6 * g as u8 + b as u8
+        }
+    }
+
     pub fn rgba(self) -> String {
         format!(
             "rgba({}, {}, {}, {})",
@@ -74,6 +113,20 @@ mod tests {
         assert_eq!(TEST_COLOR_STR, TEST_COLOR.hex());
     }
 
+    #[test]
+    fn test_color_rgb_hex() {
+        assert_eq!("#55aaff", TEST_COLOR.rgb_hex());
+    }
+
+    #[test]
+    fn test_color_ansi256() {
+        assert_eq!(75, TEST_COLOR.ansi256());
+        assert_eq!(16, Color::from(0x00_00_00_00).ansi256());
+        assert_eq!(231, Color::from(0xff_ff_ff_00).ansi256());
+        assert_eq!(196, Color::from(0xff_00_00_00).ansi256());
+        assert_eq!(244, Color::from(0x80_80_80_00).ansi256());
+    }
+
     #[test]
     fn test_color_rgb() {
         assert_eq!("rgba(85, 170, 255, 0)", TEST_COLOR.rgba());
@@ -1,5 +1,13 @@
 use super::{Error, Result};
-use crate::graph::{Highlight, Style, Weight};
+use crate::color::Color;
+use crate::forecast::Metric;
+use crate::graph::{Highlight, Scale, Style, Weight};
+use crate::provider::ProviderKind;
+use crate::template::Template;
+use chrono::format::{Item, StrftimeItems};
+use chrono_tz::Tz;
+use serde::{de, Deserialize, Deserializer};
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::prelude::*;
 use std::path::PathBuf;
@@ -9,19 +17,75 @@ use weather_icons::Style as MoonStyle;
 #[derive(Debug, Default, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct Config {
-    pub token: String,
-    pub lat: f64,
-    pub lon: f64,
+    #[serde(default)]
+    pub provider: ProviderKind,
+    pub token: Option<String>,
+    /// Send requests here instead of the provider's own server, e.g. to use a compatible
+    /// service or a mock server.
+    pub api_url: Option<String>,
+    /// Dark Sky only: the first part of the request path, `forecast` by default.
+    pub api_path: Option<String>,
+    /// Dark Sky: send Time Machine requests here instead of `api_url`. Open-Meteo: ask this
+    /// archive server for days older than the forecast API keeps.
+    pub time_machine_url: Option<String>,
+    /// NWS and MET Norway: identifies this program (and how to contact you) to the API.
+    pub user_agent: Option<String>,
+    /// Look up `--place` names here instead of nominatim.openstreetmap.org.
+    pub geocoder_url: Option<String>,
+    /// Saved locations, usable with `--location`.
+    #[serde(default)]
+    pub locations: BTreeMap<String, Location>,
+    /// The name of the location to use when none is given.
+    pub default_location: Option<String>,
+    pub lat: Option<f64>,
+    pub lon: Option<f64>,
     #[serde(rename = "font")]
     pub font_style: Option<Style>,
     #[serde(rename = "weight")]
     pub font_weight: Option<Weight>,
+    /// Polybar and lemonbar: the `n` in `%{Tn}` that selects the Sparks font in the bar's
+    /// config. These count from 1, so 2 (the default) is Polybar's `font-1`.
+    pub bar_font: Option<usize>,
+    /// tmux: whether the terminal shows 24-bit colour. When it doesn't, the highlight uses the
+    /// closest colours from the 256 colour palette. This is guessed from `$COLORTERM` if unset.
+    pub truecolor: Option<bool>,
     pub highlight: Option<Highlight>,
+    /// What to graph from the hourly forecast in the one line output, `["pressure"]` by default.
+    pub graphs: Option<Vec<Metric>>,
+    /// What to graph from the daily forecast in the `--long` output, `["temperature_high"]` by
+    /// default.
+    pub daily_graphs: Option<Vec<Metric>>,
+    /// `--long`: draw the graphs as charts this many rows tall, instead of sparklines. The
+    /// tables' `--chart`s are this tall too, or 4 rows if it's unset.
+    pub chart_height: Option<usize>,
+    pub chart_style: Option<ChartStyle>,
+    /// How each metric's graphs are scaled: "auto" to fit the values (the default), "zero" to
+    /// always include zero, `[min, max]` for a fixed range, or "shared" for one range across
+    /// every metric set to "shared".
+    #[serde(default, deserialize_with = "deserialize_scales")]
+    pub scales: BTreeMap<Metric, Scale>,
     pub local: Option<String>,
+    /// How long to reuse a forecast before asking for a new one, in seconds.
+    pub cache_ttl: Option<u64>,
+    /// Shown after an old forecast that's used because the provider couldn't be reached. This
+    /// is a `strftime` format string for the time it was fetched, e.g. "(as of %H:%M)".
+    pub stale_format: Option<String>,
+    /// The one line output as a template, e.g. "{icon} {temp:.0}{deg} {summary}", for every
+    /// output mode. See `template.rs` for the placeholders.
+    pub format: Option<String>,
+    /// Templates for particular output modes, used instead of `format`.
+    #[serde(default)]
+    pub formats: Formats,
     // Represents `pub moon_style: Option<MoonStyle>`
     #[serde(with = "MoonStyleRemote", default = "Default::default")]
     pub moon_style: MoonStyle,
     pub icon_style: Option<IconStyle>,
+    /// What `weather notify` sends notifications about.
+    #[serde(default)]
+    pub notify: Notify,
+    /// Colours for `weather chart`.
+    #[serde(default)]
+    pub svg: Svg,
 }
 
 impl Config {
@@ -31,10 +95,118 @@ impl Config {
         let mut contents = String::new();
         f.read_to_string(&mut contents)?;
 
-        toml::from_str(&contents).map_err(Error::Toml)
+        let config: Self = toml::from_str(&contents).map_err(Error::Toml)?;
+        config.check()?;
+
+        Ok(config)
+    }
+
+    /// Check the formats now, instead of after fetching the forecast. chrono panics on an
+    /// invalid time format when it's used.
+    fn check(&self) -> Result<()> {
+        for template in self.format.iter().chain(self.formats.iter()) {
+            template.parse::<Template>()?;
+        }
+
+        if let Some(ref format) = self.stale_format {
+            if StrftimeItems::new(format).any(|i| i == Item::Error) {
+                return Err(Error::InvalidTimeFormat("stale_format", format.clone()));
+            }
+        }
+
+        for timezone in self.locations.values().filter_map(|l| l.timezone.as_ref()) {
+            timezone
+                .parse::<Tz>()
+                .map_err(|_| Error::UnknownTimezone(timezone.clone()))?;
+        }
+
+        Ok(())
     }
 }
 
+/// Reads `[scales]`, whose keys are metric names. toml can only read strings as keys, so they're
+/// parsed here.
+fn deserialize_scales<'de, D>(
+    deserializer: D,
+) -> std::result::Result<BTreeMap<Metric, Scale>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    BTreeMap::<String, Scale>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(name, scale)| Ok((name.parse().map_err(de::Error::custom)?, scale)))
+        .collect()
+}
+
+/// A template for each output mode.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Formats {
+    pub plain: Option<String>,
+    pub i3blocks: Option<String>,
+    pub waybar: Option<String>,
+    pub polybar: Option<String>,
+    pub tmux: Option<String>,
+}
+
+impl Formats {
+    fn iter(&self) -> impl Iterator<Item = &String> {
+        vec![
+            &self.plain,
+            &self.i3blocks,
+            &self.waybar,
+            &self.polybar,
+            &self.tmux,
+        ]
+        .into_iter()
+        .flatten()
+    }
+}
+
+/// Settings for `weather notify`. New alerts are always notified about.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Notify {
+    /// Notify when precipitation is likely to start within this many minutes, 60 by default.
+    /// The forecast is by the hour, so this is only as precise as that: an hour counts if any
+    /// of it is this close, including the one that's already started.
+    pub precip_within: Option<u64>,
+    /// Notify when the temperature rises above or falls below any of these, in °C.
+    #[serde(default)]
+    pub temperatures: Vec<f64>,
+}
+
+/// Colours for `weather chart`, as `#rrggbbaa` like the highlight, though the alpha is left
+/// out. Unset colours are from the Tomorrow Night palette, except `now`, which is the
+/// highlight's `fg` if that's set.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Svg {
+    pub temperature: Option<Color>,
+    pub apparent_temperature: Option<Color>,
+    pub precipitation: Option<Color>,
+    pub pressure: Option<Color>,
+    pub night: Option<Color>,
+    pub now: Option<Color>,
+    pub text: Option<Color>,
+    /// Transparent if it's unset.
+    pub background: Option<Color>,
+}
+
+/// A saved location, either by coordinates or by a place name to look up.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Location {
+    pub lat: Option<f64>,
+    pub lon: Option<f64>,
+    /// Looked up by name when `lat` and `lon` aren't given, e.g. "Winnipeg, MB".
+    pub place: Option<String>,
+    /// What to call the location in the output, instead of its name in the config file.
+    pub label: Option<String>,
+    /// The location's time zone, e.g. "America/Winnipeg", for providers that don't report one.
+    pub timezone: Option<String>,
+}
+
 #[allow(dead_code)]
 #[derive(Serialize, Deserialize)]
 #[serde(remote = "MoonStyle", rename_all = "kebab-case")]
@@ -43,7 +215,7 @@ enum MoonStyleRemote {
     Alt,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Copy, Clone, Debug, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub enum IconStyle {
     WeatherIcons,
@@ -55,3 +227,62 @@ impl Default for IconStyle {
         Self::WeatherIcons
     }
 }
+
+/// How charts are drawn.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChartStyle {
+    /// A line of braille dots, two values to a character.
+    Braille,
+    /// Bars of block characters, one value to a character.
+    Blocks,
+}
+
+impl Default for ChartStyle {
+    fn default() -> Self {
+        Self::Braille
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_stale_format() {
+        let config: Config = toml::from_str(r#"stale_format = "(as of %H:%M)""#).unwrap();
+        assert!(config.check().is_ok());
+
+        let config: Config = toml::from_str(r#"stale_format = "%Q""#).unwrap();
+        match config.check() {
+            Err(Error::InvalidTimeFormat("stale_format", ref f)) if f == "%Q" => {}
+            r => panic!("expected an invalid time format error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn unknown_timezone() {
+        let config: Config = toml::from_str(
+            r#"
+            [locations.home]
+            place = "Winnipeg, MB"
+            timezone = "America/Winnipeg"
+            "#,
+        )
+        .unwrap();
+        assert!(config.check().is_ok());
+
+        let config: Config = toml::from_str(
+            r#"
+            [locations.home]
+            place = "Winnipeg, MB"
+            timezone = "Central"
+            "#,
+        )
+        .unwrap();
+        match config.check() {
+            Err(Error::UnknownTimezone(ref name)) if name == "Central" => {}
+            r => panic!("expected an unknown time zone error, got {:?}", r),
+        }
+    }
+}
@@ -1,5 +1,6 @@
+use chrono;
 use clap;
-use darksky;
+use reqwest;
 use serde_json;
 use toml;
 
@@ -11,23 +12,68 @@ use weather_icons::OutOfBounds;
 
 #[derive(Debug)]
 pub enum Error {
+    ChartTooSmall(u32, u32),
     Clap(clap::Error),
-    Darksky(darksky::Error),
+    Incomplete(&'static str),
+    InvalidLocation(String),
+    InvalidTimeFormat(&'static str, String),
     Io(io::Error),
     Json(serde_json::Error),
+    MissingLocation,
+    MissingToken(&'static str),
     Moon(OutOfBounds),
+    Notify(String),
+    Reqwest(reqwest::Error),
+    Template(String),
+    Time(chrono::ParseError),
     Toml(toml::de::Error),
+    UnknownLocation(String),
+    UnknownPlace(String),
+    UnknownTimezone(String),
+    Unsupported(&'static str),
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
+            ChartTooSmall(width, height) => write!(
+                f,
+                "the chart needs to be at least {}x{} pixels to fit its axes",
+                width, height
+            ),
             Clap(ref err) => err.fmt(f),
-            Darksky(ref err) => err.fmt(f),
+            Incomplete(part) => write!(f, "the forecast is missing {}", part),
+            InvalidLocation(ref name) => write!(
+                f,
+                "the location {:?} needs either lat and lon, or a place",
+                name
+            ),
+            InvalidTimeFormat(setting, ref format) => {
+                write!(f, "{} {:?} isn't a valid strftime format", setting, format)
+            }
             Io(ref err) => err.fmt(f),
             Json(ref err) => err.fmt(f),
+            MissingLocation => write!(
+                f,
+                "no location given; set lat and lon or default_location in the config file"
+            ),
+            MissingToken(provider) => write!(f, "the {} provider needs a token", provider),
             Moon(ref err) => err.fmt(f),
+            Notify(ref message) => write!(f, "couldn't send a notification: {}", message),
+            Reqwest(ref err) => err.fmt(f),
+            Template(ref message) => write!(f, "bad template: {}", message),
+            Time(ref err) => err.fmt(f),
             Toml(ref err) => err.fmt(f),
+            UnknownLocation(ref name) => {
+                write!(
+                    f,
+                    "there's no location called {:?} in the config file",
+                    name
+                )
+            }
+            UnknownPlace(ref place) => write!(f, "couldn't find a place called {}", place),
+            UnknownTimezone(ref name) => write!(f, "there's no time zone called {:?}", name),
+            Unsupported(feature) => write!(f, "this provider doesn't support {}", feature),
         }
     }
 }
@@ -40,12 +86,6 @@ impl From<clap::Error> for Error {
     }
 }
 
-impl From<darksky::Error> for Error {
-    fn from(err: darksky::Error) -> Self {
-        Darksky(err)
-    }
-}
-
 impl From<io::Error> for Error {
     fn from(err: io::Error) -> Self {
         Io(err)
@@ -68,6 +108,18 @@ impl From<OutOfBounds> for Error {
     }
 }
 
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        Reqwest(err)
+    }
+}
+
+impl From<chrono::ParseError> for Error {
+    fn from(err: chrono::ParseError) -> Self {
+        Time(err)
+    }
+}
+
 impl From<toml::de::Error> for Error {
     fn from(err: toml::de::Error) -> Self {
         Toml(err)
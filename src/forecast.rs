@@ -0,0 +1,576 @@
+//! A provider-neutral forecast model.
+//!
+//! Each [`Provider`](crate::provider::Provider) converts its own response into these types, so
+//! that everything downstream of fetching (formatting, graphs) doesn't need to know where the
+//! data came from.
+//!
+//! Units follow Dark Sky's `ca` unit system: temperatures in °C, speeds in km/h, pressure in
+//! hPa, precipitation intensity in mm/h and accumulation in cm. Probabilities, humidity and
+//! cloud cover are fractions between 0 and 1. Times are UNIX timestamps.
+
+use chrono::{Offset, TimeZone, Utc};
+use chrono_tz::Tz;
+use std::str::FromStr;
+
+#[derive(Clone, Debug, Default)]
+pub struct Forecast {
+    /// The name of the place this is for, if it was looked up by name.
+    pub place: Option<String>,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub timezone: Option<String>,
+    /// The location's offset from UTC in seconds, if the provider reports it.
+    pub utc_offset: Option<i32>,
+    pub current: DataPoint,
+    pub hourly: DataBlock,
+    pub daily: DataBlock,
+    pub alerts: Vec<Alert>,
+}
+
+impl Forecast {
+    /// The alerts that haven't expired by the time of the forecast.
+    pub fn active_alerts(&self) -> Vec<&Alert> {
+        let now = self.current.time;
+        self.alerts
+            .iter()
+            .filter(|a| match a.expires {
+                Some(expires) => expires > now,
+                None => true,
+            })
+            .collect()
+    }
+
+    /// Use the location's time zone from the config file when the provider doesn't report an
+    /// offset of its own.
+    pub fn default_timezone(&mut self, tz: Tz) {
+        if self.utc_offset.is_some() {
+            return;
+        }
+
+        let now = Utc.timestamp(self.current.time, 0).naive_utc();
+        self.timezone = Some(tz.name().to_string());
+        self.utc_offset = Some(tz.offset_from_utc_datetime(&now).fix().local_minus_utc());
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DataBlock {
+    pub summary: Option<String>,
+    pub data: Vec<DataPoint>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DataPoint {
+    pub time: i64,
+    pub summary: Option<String>,
+    pub icon: Option<Icon>,
+    /// MET Norway's description of the weather, e.g. `lightrainshowers_day`, which says more
+    /// than `icon` does.
+    pub symbol_code: Option<String>,
+    pub temperature: Option<f64>,
+    pub apparent_temperature: Option<f64>,
+    pub temperature_high: Option<f64>,
+    pub temperature_high_time: Option<i64>,
+    pub temperature_low: Option<f64>,
+    pub temperature_low_time: Option<i64>,
+    pub dew_point: Option<f64>,
+    pub humidity: Option<f64>,
+    pub pressure: Option<f64>,
+    pub wind_speed: Option<f64>,
+    pub wind_gust: Option<f64>,
+    pub wind_bearing: Option<f64>,
+    pub cloud_cover: Option<f64>,
+    pub uv_index: Option<f64>,
+    pub visibility: Option<f64>,
+    pub precip_intensity: Option<f64>,
+    pub precip_probability: Option<f64>,
+    pub precip_accumulation: Option<f64>,
+    pub precip_type: Option<PrecipType>,
+    pub sunrise_time: Option<i64>,
+    pub sunset_time: Option<i64>,
+    pub moon_phase: Option<f64>,
+}
+
+/// A series from the forecast that can be graphed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    Temperature,
+    ApparentTemperature,
+    TemperatureHigh,
+    TemperatureLow,
+    DewPoint,
+    Humidity,
+    Pressure,
+    WindSpeed,
+    WindGust,
+    CloudCover,
+    UvIndex,
+    PrecipProbability,
+    PrecipIntensity,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 13] = [
+        Metric::Temperature,
+        Metric::ApparentTemperature,
+        Metric::TemperatureHigh,
+        Metric::TemperatureLow,
+        Metric::DewPoint,
+        Metric::Humidity,
+        Metric::Pressure,
+        Metric::WindSpeed,
+        Metric::WindGust,
+        Metric::CloudCover,
+        Metric::UvIndex,
+        Metric::PrecipProbability,
+        Metric::PrecipIntensity,
+    ];
+
+    /// The name used in the config file and on the command line, the same as the field.
+    pub fn name(self) -> &'static str {
+        match self {
+            Metric::Temperature => "temperature",
+            Metric::ApparentTemperature => "apparent_temperature",
+            Metric::TemperatureHigh => "temperature_high",
+            Metric::TemperatureLow => "temperature_low",
+            Metric::DewPoint => "dew_point",
+            Metric::Humidity => "humidity",
+            Metric::Pressure => "pressure",
+            Metric::WindSpeed => "wind_speed",
+            Metric::WindGust => "wind_gust",
+            Metric::CloudCover => "cloud_cover",
+            Metric::UvIndex => "uv_index",
+            Metric::PrecipProbability => "precip_probability",
+            Metric::PrecipIntensity => "precip_intensity",
+        }
+    }
+
+    /// What it's called in the `--long` output.
+    pub fn description(self) -> &'static str {
+        match self {
+            Metric::Temperature => "temperature",
+            Metric::ApparentTemperature => "feels like",
+            Metric::TemperatureHigh => "high temperature",
+            Metric::TemperatureLow => "low temperature",
+            Metric::DewPoint => "dew point",
+            Metric::Humidity => "humidity",
+            Metric::Pressure => "pressure",
+            Metric::WindSpeed => "wind speed",
+            Metric::WindGust => "wind gust",
+            Metric::CloudCover => "cloud cover",
+            Metric::UvIndex => "UV index",
+            Metric::PrecipProbability => "chance of precipitation",
+            Metric::PrecipIntensity => "precipitation",
+        }
+    }
+
+    /// What it's measured in, to label charts with. Fractions like humidity don't have one.
+    pub fn unit(self) -> &'static str {
+        match self {
+            Metric::Temperature
+            | Metric::ApparentTemperature
+            | Metric::TemperatureHigh
+            | Metric::TemperatureLow
+            | Metric::DewPoint => "°",
+            Metric::Pressure => " hPa",
+            Metric::WindSpeed | Metric::WindGust => " km/h",
+            Metric::PrecipIntensity => " mm/h",
+            Metric::Humidity | Metric::CloudCover | Metric::UvIndex | Metric::PrecipProbability => {
+                ""
+            }
+        }
+    }
+
+    /// The value for each data point.
+    pub fn values(self, data: &[DataPoint]) -> Vec<Option<f64>> {
+        data.iter()
+            .map(|d| match self {
+                Metric::Temperature => d.temperature,
+                Metric::ApparentTemperature => d.apparent_temperature,
+                Metric::TemperatureHigh => d.temperature_high,
+                Metric::TemperatureLow => d.temperature_low,
+                Metric::DewPoint => d.dew_point,
+                Metric::Humidity => d.humidity,
+                Metric::Pressure => d.pressure,
+                Metric::WindSpeed => d.wind_speed,
+                Metric::WindGust => d.wind_gust,
+                Metric::CloudCover => d.cloud_cover,
+                Metric::UvIndex => d.uv_index,
+                Metric::PrecipProbability => d.precip_probability,
+                Metric::PrecipIntensity => d.precip_intensity,
+            })
+            .collect()
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Metric::ALL
+            .iter()
+            .cloned()
+            .find(|m| m.name() == name)
+            .ok_or_else(|| format!("there's no metric called {:?}", name))
+    }
+}
+
+/// The general condition of a data point.
+///
+/// This mirrors the set of icons Dark Sky used, since it's small enough for every provider to
+/// map onto.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Icon {
+    ClearDay,
+    ClearNight,
+    Cloudy,
+    Fog,
+    Hail,
+    PartlyCloudyDay,
+    PartlyCloudyNight,
+    Rain,
+    Sleet,
+    Snow,
+    Thunderstorm,
+    Tornado,
+    Wind,
+}
+
+impl Icon {
+    /// The icon's name as Dark Sky spelled it, e.g. `partly-cloudy-day`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Icon::ClearDay => "clear-day",
+            Icon::ClearNight => "clear-night",
+            Icon::Cloudy => "cloudy",
+            Icon::Fog => "fog",
+            Icon::Hail => "hail",
+            Icon::PartlyCloudyDay => "partly-cloudy-day",
+            Icon::PartlyCloudyNight => "partly-cloudy-night",
+            Icon::Rain => "rain",
+            Icon::Sleet => "sleet",
+            Icon::Snow => "snow",
+            Icon::Thunderstorm => "thunderstorm",
+            Icon::Tornado => "tornado",
+            Icon::Wind => "wind",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PrecipType {
+    Rain,
+    Sleet,
+    Snow,
+}
+
+impl PrecipType {
+    pub fn name(self) -> &'static str {
+        match self {
+            PrecipType::Rain => "rain",
+            PrecipType::Sleet => "sleet",
+            PrecipType::Snow => "snow",
+        }
+    }
+}
+
+/// A MET Norway symbol code, e.g. `lightrainshowersandthunder_day`, taken apart.
+///
+/// Codes are a kind of sky, or `[light|heavy]<rain|sleet|snow>[showers][andthunder]`, and end
+/// with the time of day when the symbol for it differs. See
+/// https://api.met.no/weatherapi/weathericon/2.0/documentation for the list of codes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SymbolCode {
+    pub kind: SymbolKind,
+    pub intensity: Option<Intensity>,
+    pub showers: bool,
+    pub thunder: bool,
+    pub daylight: Option<Daylight>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SymbolKind {
+    Clear,
+    Fair,
+    PartlyCloudy,
+    Cloudy,
+    Fog,
+    Precipitation(PrecipType),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Intensity {
+    Light,
+    Heavy,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Daylight {
+    Day,
+    Night,
+    /// The sun hasn't risen, but the sky is lit.
+    PolarTwilight,
+}
+
+impl SymbolCode {
+    /// `None` if the code isn't one of MET Norway's.
+    pub fn parse(code: &str) -> Option<Self> {
+        let mut parts = code.splitn(2, '_');
+        let condition = parts.next().unwrap_or_default();
+        let daylight = match parts.next() {
+            Some("day") => Some(Daylight::Day),
+            Some("night") => Some(Daylight::Night),
+            Some("polartwilight") => Some(Daylight::PolarTwilight),
+            _ => None,
+        };
+        let mut symbol = SymbolCode {
+            kind: SymbolKind::Cloudy,
+            intensity: None,
+            showers: false,
+            thunder: false,
+            daylight,
+        };
+
+        symbol.kind = match condition {
+            "clearsky" => SymbolKind::Clear,
+            "fair" => SymbolKind::Fair,
+            "partlycloudy" => SymbolKind::PartlyCloudy,
+            "cloudy" => SymbolKind::Cloudy,
+            "fog" => SymbolKind::Fog,
+            _ => {
+                let precipitation = |rest: &str| {
+                    if rest.starts_with("rain") {
+                        Some(PrecipType::Rain)
+                    } else if rest.starts_with("sleet") {
+                        Some(PrecipType::Sleet)
+                    } else if rest.starts_with("snow") {
+                        Some(PrecipType::Snow)
+                    } else {
+                        None
+                    }
+                };
+
+                // Some codes are misspelled with "lights" in the API, e.g.
+                // `lightssnowshowersandthunder`, so check for that first, but only when what's
+                // left is a kind of precipitation: `lightsleet` is spelled right.
+                let mut rest = condition;
+                for &(prefix, intensity) in &[
+                    ("lights", Intensity::Light),
+                    ("light", Intensity::Light),
+                    ("heavy", Intensity::Heavy),
+                ] {
+                    if condition.starts_with(prefix)
+                        && precipitation(&condition[prefix.len()..]).is_some()
+                    {
+                        symbol.intensity = Some(intensity);
+                        rest = &condition[prefix.len()..];
+                        break;
+                    }
+                }
+
+                let precip_type = precipitation(rest)?;
+                symbol.showers = rest.contains("showers");
+                symbol.thunder = rest.ends_with("andthunder");
+                SymbolKind::Precipitation(precip_type)
+            }
+        };
+
+        Some(symbol)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Advisory,
+    Watch,
+    Warning,
+}
+
+impl Severity {
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Advisory => "advisory",
+            Severity::Watch => "watch",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Alert {
+    pub title: String,
+    pub description: String,
+    pub time: i64,
+    pub expires: Option<i64>,
+    pub severity: Option<Severity>,
+    pub uri: Option<String>,
+    pub regions: Vec<String>,
+}
+
+/// Estimate the moon phase at `time`, as a fraction of a lunation: 0 is a new moon, 0.5 is a
+/// full moon.
+///
+/// This is for providers that don't report it. It counts synodic months since a known new moon,
+/// which is accurate to within about a day.
+pub fn moon_phase(time: i64) -> f64 {
+    // 2000-01-06 18:14 UTC
+    const NEW_MOON: i64 = 947_182_440;
+    const SYNODIC_MONTH: f64 = 29.530_588_853 * 24. * 60. * 60.;
+
+    ((time - NEW_MOON) as f64 / SYNODIC_MONTH).rem_euclid(1.)
+}
+
+/// The name of the moon phase nearest to `phase`, e.g. "waxing crescent".
+pub fn moon_phase_name(phase: f64) -> &'static str {
+    const NAMES: [&str; 8] = [
+        "new moon",
+        "waxing crescent",
+        "first quarter",
+        "waxing gibbous",
+        "full moon",
+        "waning gibbous",
+        "last quarter",
+        "waning crescent",
+    ];
+
+    NAMES[(phase.rem_euclid(1.) * 8.).round() as usize % NAMES.len()]
+}
+
+/// Whether the sun is up at `time`, going by the sunrise and sunset of the day in `daily` it
+/// falls in.
+pub fn is_day(time: i64, daily: &[DataPoint]) -> Option<bool> {
+    const DAY: i64 = 24 * 60 * 60;
+
+    daily
+        .iter()
+        .filter(|d| d.time <= time && time < d.time + DAY)
+        .find_map(|d| match (d.sunrise_time, d.sunset_time) {
+            (Some(sunrise), Some(sunset)) => Some(sunrise <= time && time <= sunset),
+            _ => None,
+        })
+}
+
+/// Calculate sunrise and sunset for the day containing `time`, for providers that don't report
+/// them.
+///
+/// Returns `None` during polar day or night. This follows the sunrise equation described at
+/// https://en.wikipedia.org/wiki/Sunrise_equation, which is good to within a few minutes.
+pub fn sun_times(time: i64, lat: f64, lon: f64) -> Option<(i64, i64)> {
+    const UNIX_EPOCH_JULIAN_DAY: f64 = 2_440_587.5;
+    const J2000: f64 = 2_451_545.;
+
+    let julian_day = time as f64 / 86_400. + UNIX_EPOCH_JULIAN_DAY;
+    let n = (julian_day - J2000 + 0.0008).ceil();
+
+    let mean_solar_noon = n - lon / 360.;
+    let mean_anomaly = (357.5291 + 0.985_600_28 * mean_solar_noon) % 360.;
+    let m = mean_anomaly.to_radians();
+    let center = 1.9148 * m.sin() + 0.02 * (2. * m).sin() + 0.0003 * (3. * m).sin();
+    let ecliptic_longitude = ((mean_anomaly + center + 180. + 102.9372) % 360.).to_radians();
+    let transit =
+        J2000 + mean_solar_noon + 0.0053 * m.sin() - 0.0069 * (2. * ecliptic_longitude).sin();
+
+    let declination = (ecliptic_longitude.sin() * 23.44_f64.to_radians().sin()).asin();
+    let lat = lat.to_radians();
+    let cos_hour_angle = ((-0.833_f64).to_radians().sin() - lat.sin() * declination.sin())
+        / (lat.cos() * declination.cos());
+    if cos_hour_angle.abs() > 1. {
+        return None;
+    }
+    let hour_angle = cos_hour_angle.acos().to_degrees();
+
+    let to_unix = |j: f64| ((j - UNIX_EPOCH_JULIAN_DAY) * 86_400.).round() as i64;
+    Some((
+        to_unix(transit - hour_angle / 360.),
+        to_unix(transit + hour_angle / 360.),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moon_phase_estimate() {
+        // New moon: 2019-01-06 01:28 UTC
+        let phase = moon_phase(1_546_738_080);
+        assert!(!(0.03..=0.97).contains(&phase), "{}", phase);
+
+        // Full moon: 2019-01-21 05:16 UTC
+        assert!((moon_phase(1_548_047_760) - 0.5).abs() < 0.03);
+    }
+
+    #[test]
+    fn expired_alerts() {
+        let alert = |title: &str, expires| Alert {
+            title: title.to_string(),
+            expires,
+            ..Alert::default()
+        };
+        let weather = Forecast {
+            current: DataPoint {
+                time: 100,
+                ..DataPoint::default()
+            },
+            alerts: vec![
+                alert("expired", Some(50)),
+                alert("active", Some(150)),
+                alert("open-ended", None),
+            ],
+            ..Forecast::default()
+        };
+
+        let titles: Vec<_> = weather
+            .active_alerts()
+            .iter()
+            .map(|a| a.title.as_str())
+            .collect();
+        assert_eq!(vec!["active", "open-ended"], titles);
+    }
+
+    #[test]
+    fn metric_names() {
+        for &metric in &Metric::ALL {
+            assert_eq!(Ok(metric), metric.name().parse());
+        }
+        assert!("pressures".parse::<Metric>().is_err());
+    }
+
+    #[test]
+    fn moon_phase_names() {
+        assert_eq!("new moon", moon_phase_name(0.));
+        assert_eq!("new moon", moon_phase_name(0.98));
+        assert_eq!("waxing crescent", moon_phase_name(0.1));
+        assert_eq!("first quarter", moon_phase_name(0.25));
+        assert_eq!("full moon", moon_phase_name(0.5));
+        assert_eq!("waning crescent", moon_phase_name(0.85));
+    }
+
+    #[test]
+    fn is_day_from_daily_sun_times() {
+        let daily = vec![DataPoint {
+            time: 0,
+            sunrise_time: Some(6 * 60 * 60),
+            sunset_time: Some(18 * 60 * 60),
+            ..DataPoint::default()
+        }];
+        assert_eq!(Some(false), is_day(60 * 60, &daily));
+        assert_eq!(Some(true), is_day(12 * 60 * 60, &daily));
+        assert_eq!(None, is_day(30 * 60 * 60, &daily));
+    }
+
+    #[test]
+    fn sun_times_winnipeg() {
+        // 2019-06-01 00:00 CDT. Sunrise was at 05:22 and sunset at 21:31.
+        let (sunrise, sunset) = sun_times(1_559_365_200, 49.9, -97.14).unwrap();
+        assert!((sunrise - 1_559_384_520).abs() < 5 * 60, "{}", sunrise);
+        assert!((sunset - 1_559_442_660).abs() < 5 * 60, "{}", sunset);
+    }
+
+    #[test]
+    fn sun_times_polar_night() {
+        // 2019-12-21 in Utqiaġvik, Alaska.
+        assert_eq!(None, sun_times(1_576_918_800, 71.29, -156.79));
+    }
+}
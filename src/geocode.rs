@@ -0,0 +1,151 @@
+//! Looking up coordinates by place name, using [Nominatim](https://nominatim.org).
+
+use reqwest::header::USER_AGENT;
+use reqwest::Client;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::cache;
+use crate::provider;
+use crate::{Config, Error, Result};
+
+pub const API_URL: &str = "https://nominatim.openstreetmap.org";
+
+/// Every place looked up so far, keyed by the query. Places don't move, so these never expire.
+const CACHE_NAME: &str = "places.json";
+
+/// A place found by name.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Place {
+    /// The full name of the place, e.g. "Winnipeg, Manitoba, Canada".
+    pub name: String,
+    pub lat: f64,
+    pub lon: f64,
+}
+
+/// One result from Nominatim's `/search`. Coordinates are given as strings.
+#[derive(Debug, Deserialize)]
+struct SearchResult {
+    display_name: String,
+    lat: String,
+    lon: String,
+}
+
+pub struct Geocoder {
+    client: Client,
+    url: String,
+    user_agent: String,
+    cache_dir: Option<PathBuf>,
+}
+
+impl Geocoder {
+    pub fn new(url: &str, user_agent: &str, cache_dir: Option<PathBuf>) -> Self {
+        Self {
+            client: Client::new(),
+            url: url.trim_end_matches('/').to_string(),
+            user_agent: user_agent.to_string(),
+            cache_dir,
+        }
+    }
+
+    /// Find the coordinates of `query`, e.g. "Winnipeg, MB".
+    pub fn lookup(&self, query: &str) -> Result<Place> {
+        let key = query.trim().to_lowercase();
+
+        let mut places: BTreeMap<String, Place> = self
+            .cache_dir
+            .as_ref()
+            .and_then(|dir| cache::read(dir, CACHE_NAME))
+            .unwrap_or_default();
+
+        if let Some(place) = places.get(&key) {
+            info!("using cached location for {:?}: {}", query, place.name);
+            return Ok(place.clone());
+        }
+
+        let url = format!("{}/search", self.url);
+        debug!("GET {} q={:?}", url, query);
+        let results: Vec<SearchResult> = self
+            .client
+            .get(&url)
+            .header(USER_AGENT, &self.user_agent[..])
+            .query(&[("q", query), ("format", "jsonv2"), ("limit", "1")])
+            .send()?
+            .error_for_status()?
+            .json()?;
+
+        let place = results
+            .into_iter()
+            .filter_map(|r| {
+                Some(Place {
+                    lat: r.lat.parse().ok()?,
+                    lon: r.lon.parse().ok()?,
+                    name: r.display_name,
+                })
+            })
+            .next()
+            .ok_or_else(|| Error::UnknownPlace(query.to_string()))?;
+        info!("found {:?} at {},{}", place.name, place.lat, place.lon);
+
+        if let Some(ref dir) = self.cache_dir {
+            places.insert(key, place.clone());
+            if let Err(e) = cache::write(dir, CACHE_NAME, &places) {
+                warn!("couldn't cache location: {}", e);
+            }
+        }
+
+        Ok(place)
+    }
+}
+
+pub fn from_config(config: &Config) -> Geocoder {
+    Geocoder::new(
+        config.geocoder_url.as_ref().map_or(API_URL, String::as_str),
+        config
+            .user_agent
+            .as_ref()
+            .map_or(provider::DEFAULT_USER_AGENT, String::as_str),
+        cache::dir(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{Route, TestServer};
+
+    const FIXTURE: &str = include_str!("../tests/fixtures/nominatim.json");
+
+    #[test]
+    fn lookup_is_cached() {
+        let server = TestServer::new(vec![Route::new("/search", FIXTURE)]);
+        let dir = cache::test_dir("geocode-cached");
+        let geocoder = Geocoder::new(&server.url, "weather-test", Some(dir));
+
+        let expected = Place {
+            name: "Winnipeg, Manitoba, Canada".to_string(),
+            lat: 49.8955367,
+            lon: -97.1384584,
+        };
+        assert_eq!(expected, geocoder.lookup("Winnipeg, MB").unwrap());
+        assert_eq!(expected, geocoder.lookup("winnipeg, mb ").unwrap());
+
+        let requests = server.requests();
+        assert_eq!(1, requests.len());
+        assert!(requests[0].starts_with("GET /search?q=Winnipeg%2C+MB&format=jsonv2&limit=1 "));
+        assert!(requests[0]
+            .to_lowercase()
+            .contains("user-agent: weather-test"));
+    }
+
+    #[test]
+    fn lookup_unknown_place() {
+        let server = TestServer::new(vec![Route::new("/search", "[]")]);
+        let geocoder = Geocoder::new(&server.url, "weather-test", None);
+
+        match geocoder.lookup("Nowhere") {
+            Err(Error::UnknownPlace(ref query)) if query == "Nowhere" => {}
+            r => panic!("expected an unknown place error, got {:?}", r),
+        }
+    }
+}
@@ -1,6 +1,10 @@
+mod axis;
+mod blocks;
+mod braille;
 mod font;
 
 use ansi_term;
+use serde::{de, Deserialize, Deserializer};
 
 pub use self::font::Style;
 use self::font::SPARKS_FONT_SIZE;
@@ -15,11 +19,132 @@ pub struct Highlight {
     pub bg: Option<Color>,
 }
 
+impl Highlight {
+    /// The terminal style for highlighted text.
+    pub fn style(&self) -> ansi_term::Style {
+        let mut style = ansi_term::Style::default();
+
+        if let Some(f) = self.fg {
+            style = style.fg(ansi_term::Colour::RGB(f.red, f.green, f.blue));
+        }
+
+        if let Some(b) = self.bg {
+            style = style.on(ansi_term::Colour::RGB(b.red, b.green, b.blue));
+        }
+
+        style
+    }
+}
+
+/// How a graph fits its values to its height.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Scale {
+    /// From the lowest value to the highest.
+    Auto,
+    /// From zero to the values, so they can be compared by size.
+    Zero,
+    /// Between a minimum and a maximum. Values outside it are drawn at the edge, and flagged.
+    Fixed(f64, f64),
+    /// The same range as other graphs with a shared scale. Whatever draws several graphs works
+    /// that out and gives each a fixed scale; on its own, this is the same as `Auto`.
+    Shared,
+}
+
+impl Default for Scale {
+    fn default() -> Self {
+        Scale::Auto
+    }
+}
+
+/// Either `"auto"`, `"zero"`, `"shared"`, or `[min, max]`.
+impl<'de> Deserialize<'de> for Scale {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Setting {
+            Name(String),
+            Range(f64, f64),
+        }
+
+        match Setting::deserialize(deserializer)? {
+            Setting::Name(name) => match name.as_str() {
+                "auto" => Ok(Scale::Auto),
+                "zero" => Ok(Scale::Zero),
+                "shared" => Ok(Scale::Shared),
+                _ => Err(de::Error::custom(format!(
+                    "unknown scale {:?}, expected \"auto\", \"zero\", \"shared\" or [min, max]",
+                    name
+                ))),
+            },
+            Setting::Range(min, max) if min < max => Ok(Scale::Fixed(min, max)),
+            Setting::Range(min, max) => Err(de::Error::custom(format!(
+                "a scale's min ({}) needs to be less than its max ({})",
+                min, max
+            ))),
+        }
+    }
+}
+
+/// Which way a value is outside a fixed scale.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum OutOfRange {
+    Above,
+    Below,
+}
+
+impl OutOfRange {
+    fn check(value: f64, min: f64, max: f64) -> Option<Self> {
+        if value > max {
+            Some(OutOfRange::Above)
+        } else if value < min {
+            Some(OutOfRange::Below)
+        } else {
+            None
+        }
+    }
+
+    /// Drawn in place of the value, or under it, to flag it.
+    fn arrow(self) -> char {
+        match self {
+            OutOfRange::Above => '↑',
+            OutOfRange::Below => '↓',
+        }
+    }
+}
+
+/// An arrow for each value outside `min` and `max`, by index.
+fn flags(values: &[Option<f64>], min: f64, max: f64) -> Vec<(usize, char)> {
+    values
+        .iter()
+        .enumerate()
+        .filter_map(|(i, v)| {
+            v.and_then(|v| OutOfRange::check(v, min, max))
+                .map(|o| (i, o.arrow()))
+        })
+        .collect()
+}
+
+/// Each value moved to the edge of `min` and `max` if it's outside them.
+fn clamp(values: &[Option<f64>], min: f64, max: f64) -> Vec<Option<f64>> {
+    values
+        .iter()
+        .map(|v| v.map(|v| v.max(min).min(max)))
+        .collect()
+}
+
 #[derive(Debug, Default)]
 pub struct Graph {
     values: Vec<Option<f64>>,
     pub font: Font,
     pub highlight: Option<Highlight>,
+    /// Labels for the x axis of charts, by value index.
+    ticks: Vec<(usize, String)>,
+    /// What the values are measured in, for labelling the y axis of charts.
+    unit: String,
+    scale: Scale,
 }
 
 impl Graph {
@@ -28,6 +153,9 @@ impl Graph {
             values: Vec::new(),
             font: Font::default(),
             highlight: None,
+            ticks: Vec::new(),
+            unit: String::new(),
+            scale: Scale::Auto,
         }
     }
 
@@ -48,6 +176,21 @@ impl Graph {
         self
     }
 
+    pub fn ticks(&mut self, ticks: &[(usize, String)]) -> &mut Self {
+        self.ticks = ticks.to_vec();
+        self
+    }
+
+    pub fn unit(&mut self, unit: &str) -> &mut Self {
+        self.unit = unit.to_string();
+        self
+    }
+
+    pub fn scale(&mut self, scale: Scale) -> &mut Self {
+        self.scale = scale;
+        self
+    }
+
     pub fn font_style(&mut self, font_style: &Style) -> &mut Self {
         self.font.style = *font_style;
         self
@@ -61,62 +204,105 @@ impl Graph {
     // Giving credit where credit is due: this was heavily inspired by Jiři Šebele's work:
     // https://github.com/jiri/rust-spark.
     pub fn sparkline(&self) -> String {
-        use ansi_term::Style;
-        let bars = "▁▂▃▄▅▆▇█";
+        let mut graph = self.bars();
 
-        let (min, _, ratio) = calculate_min_max_and_ratio(&self.values, bars.chars().count() - 1);
+        if let Some(ref h) = self.highlight {
+            if let Some(p) = h.position {
+                graph[p] = h.style().paint(graph[p].clone()).to_string();
+            }
+        }
 
-        let mut graph = self
-            .values
-            .iter()
-            .map(|value| {
-                if let Some(i) = *value {
-                    bars.chars()
-                        .nth(((i - min) * ratio).floor() as usize)
-                        .unwrap_or_else(|| panic!("{} is out of bounds", i))
-                        .to_string()
-                } else {
-                    " ".to_string()
-                }
-            })
-            .collect::<Vec<String>>();
+        graph.into_iter().collect::<String>()
+    }
+
+    /// A bar chart `height` rows tall, one character per value, with the highest and lowest
+    /// values and zero labelled on the y axis, and the ticks on the x axis. The highlighted
+    /// value is marked with a column down the chart.
+    pub fn chart(&self, height: usize) -> String {
+        let (min, max, _) = calculate_min_max_and_ratio(&self.values, 1, self.scale);
+        blocks::chart(
+            &self.values,
+            (min, max),
+            &self.unit,
+            height,
+            &self.ticks,
+            self.highlight.as_ref(),
+        )
+    }
+
+    /// A line chart `height` rows tall, drawn in braille dots, with the range on the y axis and
+    /// the ticks on the x axis. The highlighted value is marked with a column down the chart.
+    ///
+    /// Without a `width`, each character holds two values.
+    pub fn braille(&self, height: usize, width: Option<usize>) -> String {
+        let n = self.values.len();
+        let width = width.unwrap_or(n / 2 + n % 2);
+        let (min, max, _) = calculate_min_max_and_ratio(&self.values, 1, self.scale);
+        braille::chart(
+            &self.values,
+            (min, max),
+            &self.unit,
+            width,
+            height,
+            &self.ticks,
+            self.highlight.as_ref(),
+        )
+    }
+
+    /// Like `sparkline`, but highlighted with tmux's `#[fg=…,bg=…]` style codes.
+    ///
+    /// Without `truecolor`, the highlight uses the closest colours in the 256 colour palette.
+    pub fn tmux_sparkline(&self, truecolor: bool) -> String {
+        let mut graph = self.bars();
 
         if let Some(ref h) = self.highlight {
             if let Some(p) = h.position {
-                let mut style = Style::default();
-
-                if let Some(f) = h.fg {
-                    style = style.fg(ansi_term::Colour::RGB(f.red, f.green, f.blue));
-                }
+                let colour = |c: Color| {
+                    if truecolor {
+                        c.rgb_hex()
+                    } else {
+                        format!("colour{}", c.ansi256())
+                    }
+                };
+                let style =
+                    h.fg.map(|f| format!("fg={}", colour(f)))
+                        .into_iter()
+                        .chain(h.bg.map(|b| format!("bg={}", colour(b))))
+                        .collect::<Vec<_>>();
 
-                if let Some(b) = h.bg {
-                    style = style.on(ansi_term::Colour::RGB(b.red, b.green, b.blue));
+                if !style.is_empty() {
+                    graph[p] = format!("#[{}]{}#[default]", style.join(","), graph[p]);
                 }
-
-                graph[p] = style.paint(graph[p].clone()).to_string();
             }
         }
 
         graph.into_iter().collect::<String>()
     }
 
-    // Giving credit where credit is due: this was heavily inspired by Jiři Šebele's work:
-    // https://github.com/jiri/rust-spark.
-    pub fn sparkfont(&self) -> String {
-        let (min, _, ratio) = calculate_min_max_and_ratio(&self.values, SPARKS_FONT_SIZE);
+    /// Each value as one of the block characters used by `sparkline`, or an arrow if it's
+    /// outside a fixed scale.
+    fn bars(&self) -> Vec<String> {
+        let bars: Vec<char> = "▁▂▃▄▅▆▇█".chars().collect();
 
-        let mut graph = self
-            .values
-            .iter()
-            .map(|n| n.unwrap_or_else(|| 0.))
-            .map(|n| (n - min) * ratio)
-            .map(|n| format!("{},", n.floor()))
-            .collect::<Vec<String>>();
+        let (min, max, ratio) =
+            calculate_min_max_and_ratio(&self.values, bars.len() - 1, self.scale);
 
-        graph[0] = format!("{{{}", graph[0].clone());
+        self.values
+            .iter()
+            .map(|value| match *value {
+                Some(i) => match OutOfRange::check(i, min, max) {
+                    Some(o) => o.arrow().to_string(),
+                    None => bars[((i - min) * ratio).floor() as usize].to_string(),
+                },
+                None => " ".to_string(),
+            })
+            .collect()
+    }
 
-        let last = graph.len() - 1;
-        graph[last] = graph[last].replace(",", "}");
+    // Giving credit where credit is due: this was heavily inspired by Jiři Šebele's work:
+    // https://github.com/jiri/rust-spark.
+    pub fn sparkfont(&self) -> String {
+        let mut graph = self.sparks();
 
         if let Some(ref h) = self.highlight {
             if let Some(p) = h.position {
@@ -133,14 +319,93 @@ impl Graph {
         }
 
         format!(
-            "<span font_desc='{}'>{}</span>",
+            "<span font_desc='{}'>{}</span>{}",
             self.font,
             graph.into_iter().collect::<String>(),
+            self.clamped(),
         )
     }
+
+    /// Like `sparkfont`, but formatted for Polybar and lemonbar.
+    ///
+    /// These bars pick fonts by their position in the bar's config rather than by name, so
+    /// `font_index` is the `n` in `%{Tn}` for the Sparks font named by `self.font`.
+    pub fn sparkbar(&self, font_index: usize) -> String {
+        let mut graph = self.sparks();
+
+        if let Some(ref h) = self.highlight {
+            if let Some(p) = h.position {
+                let mut highlighted = graph[p].clone();
+                if let Some(f) = h.fg {
+                    highlighted = format!("%{{F{}}}{}%{{F-}}", f.rgb_hex(), highlighted);
+                }
+                if let Some(b) = h.bg {
+                    highlighted = format!("%{{B{}}}{}%{{B-}}", b.rgb_hex(), highlighted);
+                }
+                graph[p] = highlighted;
+            }
+        }
+
+        format!(
+            "%{{T{}}}{}%{{T-}}{}",
+            font_index,
+            graph.into_iter().collect::<String>(),
+            self.clamped(),
+        )
+    }
+
+    /// Each value as the text the Sparks font draws as a bar, e.g. `{10,` `50,` `100}`.
+    ///
+    /// The font only draws bars, so values outside a fixed scale are drawn at the edge of it,
+    /// and flagged after the graph by `clamped`.
+    fn sparks(&self) -> Vec<String> {
+        let mut graph = if self.values.iter().all(Option::is_none) {
+            // There's no range to scale to, so every bar is empty.
+            vec!["0,".to_string(); self.values.len()]
+        } else {
+            let (min, max, ratio) =
+                calculate_min_max_and_ratio(&self.values, SPARKS_FONT_SIZE, self.scale);
+
+            self.values
+                .iter()
+                .map(|n| n.unwrap_or_else(|| 0.))
+                .map(|n| (n.max(min).min(max) - min) * ratio)
+                .map(|n| format!("{},", n.floor()))
+                .collect::<Vec<String>>()
+        };
+        if graph.is_empty() {
+            return graph;
+        }
+
+        graph[0] = format!("{{{}", graph[0].clone());
+
+        let last = graph.len() - 1;
+        graph[last] = graph[last].replace(",", "}");
+
+        graph
+    }
+
+    /// A `↑` if any value is above a fixed scale, and a `↓` if any is below it, to follow a
+    /// graph drawn with `sparks`.
+    fn clamped(&self) -> String {
+        let (min, max, _) = calculate_min_max_and_ratio(&self.values, SPARKS_FONT_SIZE, self.scale);
+        let mut arrows: Vec<char> = flags(&self.values, min, max)
+            .into_iter()
+            .map(|(_, arrow)| arrow)
+            .collect();
+        arrows.sort();
+        arrows.dedup();
+        arrows.into_iter().collect()
+    }
 }
 
-fn calculate_min_max_and_ratio(values: &[Option<f64>], size: usize) -> (f64, f64, f64) {
+/// The range `values` are drawn over by `scale`, and how many of `size` steps there are to
+/// each unit.
+fn calculate_min_max_and_ratio(
+    values: &[Option<f64>],
+    size: usize,
+    scale: Scale,
+) -> (f64, f64, f64) {
     let mut min = ::std::f64::MAX;
     let mut max = ::std::f64::MIN;
 
@@ -154,6 +419,18 @@ fn calculate_min_max_and_ratio(values: &[Option<f64>], size: usize) -> (f64, f64
         }
     });
 
+    match scale {
+        Scale::Auto | Scale::Shared => {}
+        Scale::Zero => {
+            min = min.min(0.);
+            max = max.max(0.);
+        }
+        Scale::Fixed(low, high) => {
+            min = low;
+            max = high;
+        }
+    }
+
     // Compare if max and min are equal, as suggested by clippy
     let ratio = if (max - min).abs() < ::std::f64::EPSILON {
         1.
@@ -165,3 +442,112 @@ fn calculate_min_max_and_ratio(values: &[Option<f64>], size: usize) -> (f64, f64
 
     (min, max, ratio)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tmux_sparkline_highlight() {
+        let mut graph = Graph::new();
+        graph.values(&[Some(1.), Some(2.), Some(3.)]);
+        assert_eq!("▁▄█", graph.tmux_sparkline(true));
+
+        graph.highlight(
+            &Some(1),
+            &Highlight {
+                position: None,
+                fg: Some(Color::from(0x55_aa_ff_00)),
+                bg: None,
+            },
+        );
+        assert_eq!("▁#[fg=#55aaff]▄#[default]█", graph.tmux_sparkline(true));
+        assert_eq!("▁#[fg=colour75]▄#[default]█", graph.tmux_sparkline(false));
+    }
+
+    #[test]
+    fn sparkbar_highlight() {
+        let mut graph = Graph::new();
+        graph.values(&[Some(1.), Some(2.), Some(3.)]);
+        assert_eq!("%{T2}{0,50,100}%{T-}", graph.sparkbar(2));
+
+        graph.highlight(
+            &Some(2),
+            &Highlight {
+                position: None,
+                fg: Some(Color::from(0x55_aa_ff_00)),
+                bg: Some(Color::from(0x11_22_33_00)),
+            },
+        );
+        assert_eq!(
+            "%{T3}{0,50,%{B#112233}%{F#55aaff}100}%{F-}%{B-}%{T-}",
+            graph.sparkbar(3)
+        );
+    }
+
+    #[test]
+    fn sparkline_scales() {
+        let mut graph = Graph::new();
+        graph.values(&[Some(1012.), Some(1013.), Some(1014.)]);
+        assert_eq!("▁▄█", graph.sparkline());
+
+        // A flat day looks flat on a fixed scale.
+        graph.scale(Scale::Fixed(980., 1040.));
+        assert_eq!("▄▄▄", graph.sparkline());
+
+        graph.scale(Scale::Zero);
+        assert_eq!("▇▇█", graph.sparkline());
+    }
+
+    #[test]
+    fn out_of_range_flagged() {
+        let mut graph = Graph::new();
+        graph
+            .values(&[Some(970.), Some(1000.), Some(1050.)])
+            .scale(Scale::Fixed(980., 1040.));
+        assert_eq!("↓▃↑", graph.sparkline());
+        assert_eq!("{0,33,100}", graph.sparks().concat());
+        assert!(graph.sparkfont().ends_with("</span>↑↓"));
+        assert!(graph.sparkbar(2).ends_with("%{T-}↑↓"));
+
+        graph.values(&[Some(1000.), Some(1010.)]);
+        assert!(graph.sparkfont().ends_with("</span>"));
+    }
+
+    #[test]
+    fn no_values() {
+        let mut graph = Graph::new();
+        graph.values(&[None, None, None]);
+        assert_eq!("{0,0,0}", graph.sparks().concat());
+
+        graph.scale(Scale::Zero);
+        assert!(!graph.sparkfont().contains("NaN"), "{}", graph.sparkfont());
+        assert_eq!("   ", graph.sparkline());
+
+        graph.values(&[]);
+        assert!(graph.sparks().is_empty());
+    }
+
+    #[test]
+    fn scale_settings() {
+        #[derive(Deserialize)]
+        struct Settings {
+            scales: Vec<Scale>,
+        }
+        let settings: Settings =
+            toml::from_str(r#"scales = ["auto", "zero", "shared", [980, 1040.5]]"#).unwrap();
+        assert_eq!(
+            vec![
+                Scale::Auto,
+                Scale::Zero,
+                Scale::Shared,
+                Scale::Fixed(980., 1040.5)
+            ],
+            settings.scales
+        );
+
+        let error = |s: &str| toml::from_str::<Settings>(s).err().unwrap().to_string();
+        assert!(error(r#"scales = ["log"]"#).contains("unknown scale \"log\""));
+        assert!(error("scales = [[10, 0]]").contains("needs to be less than its max"));
+    }
+}
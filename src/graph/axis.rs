@@ -0,0 +1,91 @@
+//! The axes drawn around charts.
+
+/// The y axis beside a row with a label, and beside one without.
+pub(super) const LABELLED: char = '┤';
+pub(super) const UNLABELLED: char = '│';
+
+/// How many decimals labels need for the difference between `min` and `max` to show.
+pub(super) fn precision(min: f64, max: f64) -> usize {
+    match max - min {
+        r if r >= 10. => 0,
+        r if r >= 1. => 1,
+        _ => 2,
+    }
+}
+
+/// A value with its unit, for labelling an axis.
+pub(super) fn label(value: f64, precision: usize, unit: &str) -> String {
+    format!("{:.*}{}", precision, value, unit)
+}
+
+/// A row of a chart, with its label right-aligned to `label_width` and the y axis after it.
+pub(super) fn row(label: Option<&str>, label_width: usize, cells: &str) -> String {
+    let axis = if label.is_some() {
+        LABELLED
+    } else {
+        UNLABELLED
+    };
+    let row = format!(
+        "{:>w$} {}{}",
+        label.unwrap_or(""),
+        axis,
+        cells,
+        w = label_width
+    );
+    row.trim_end().to_string()
+}
+
+/// The x axis under a chart `width` characters wide, with a tick under each labelled
+/// character, the `flags` under characters with values out of range, and a `▲` under the
+/// `marked` one. The labels go underneath, leaving out any that would run into the one before.
+pub(super) fn x_axis(
+    label_width: usize,
+    width: usize,
+    ticks: &[(usize, &str)],
+    flags: &[(usize, char)],
+    marked: Option<usize>,
+) -> Vec<String> {
+    let axis: String = (0..width)
+        .map(|x| {
+            if marked == Some(x) {
+                '▲'
+            } else if let Some(&(_, flag)) = flags.iter().find(|&&(f, _)| f == x) {
+                flag
+            } else if ticks.iter().any(|&(t, _)| t == x) {
+                '┬'
+            } else {
+                '─'
+            }
+        })
+        .collect();
+    let mut lines = vec![format!("{:w$} └{}", "", axis, w = label_width)];
+
+    let mut labels = String::new();
+    for &(x, label) in ticks {
+        let column = label_width + 2 + x;
+        let len = labels.chars().count();
+        if len == 0 || column > len {
+            labels.push_str(&" ".repeat(column - len));
+            labels.push_str(label);
+        }
+    }
+    if !labels.trim().is_empty() {
+        lines.push(labels);
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crowded_ticks_left_out() {
+        let ticks = [(0, "Sat"), (2, "Sun"), (4, "Mon")];
+        assert_eq!(
+            vec!["  └┬↑┬─┬▲".to_string(), "   Sat Mon".to_string()],
+            x_axis(1, 6, &ticks, &[(1, '↑')], Some(5))
+        );
+    }
+}
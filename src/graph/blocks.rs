@@ -0,0 +1,250 @@
+//! Bar charts stacked from block characters over several rows. The eighth blocks give each bar
+//! a precision of an eighth of a row.
+
+use super::{axis, clamp, flags, Highlight};
+
+/// Blocks filling a character from the bottom, by eighths.
+const LOWER: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Draw `values` as bars `height` rows tall, one character each, scaled to `range`, with the
+/// highest and lowest values labelled in `unit` on the y axis where their bars end, and `ticks`
+/// on the x axis. Values outside the range are drawn to its edge, with an arrow under them.
+///
+/// When the values cross zero, bars go up or down from it. When they're all below zero, they
+/// hang from the top. Unicode only has blocks filling the top of a character by an eighth or a
+/// half, so bars going down are only as precise as that.
+pub(super) fn chart(
+    values: &[Option<f64>],
+    range: (f64, f64),
+    unit: &str,
+    height: usize,
+    ticks: &[(usize, String)],
+    highlight: Option<&Highlight>,
+) -> String {
+    if height == 0 || values.iter().all(Option::is_none) {
+        return String::new();
+    }
+
+    let levels = height * 8;
+    let (min, max) = range;
+    let flags = flags(values, min, max);
+    let values = clamp(values, min, max);
+    let (lowest, highest) = values
+        .iter()
+        .filter_map(|v| *v)
+        .fold((max, min), |(low, high), v| (v.min(low), v.max(high)));
+    let crosses_zero = min < 0. && max > 0. && height > 1;
+
+    // Where the bars start, and how many eighths they grow by for each unit.
+    let (base, scale) = if crosses_zero {
+        // Keep zero between rows, so bars going either way start from the edge of a character.
+        let below = ((-min / (max - min) * height as f64).round() as usize).max(1);
+        let below = below.min(height - 1);
+        let scale = f64::min(
+            ((height - below) * 8) as f64 / max,
+            (below * 8) as f64 / -min,
+        );
+        (below * 8, scale)
+    } else if max > min {
+        let base = if max <= 0. { levels } else { 0 };
+        (base, levels as f64 / (max - min))
+    } else {
+        (0, 0.)
+    };
+    // The eighth a value reaches, counting up from the bottom.
+    let eighth = |v: f64| {
+        let from = if crosses_zero {
+            0.
+        } else if base == 0 {
+            min
+        } else {
+            max
+        };
+        (base as f64 + (v - from) * scale)
+            .round()
+            .max(0.)
+            .min(levels as f64) as usize
+    };
+
+    // The eighths each bar covers. Every value gets at least one so it shows up.
+    let bars: Vec<Option<(usize, usize)>> = values
+        .iter()
+        .map(|v| {
+            v.map(|v| {
+                let end = eighth(v);
+                let up = if crosses_zero { v >= 0. } else { base == 0 };
+                if up {
+                    (base, end.max(base + 1))
+                } else {
+                    (end.min(base - 1), base)
+                }
+            })
+        })
+        .collect();
+
+    let marked = highlight.and_then(|h| h.position);
+    let precision = axis::precision(min, max);
+    let row_of = |eighth: usize| height - 1 - eighth.min(levels - 1) / 8;
+
+    // Label the rows the highest and lowest bars end in, and zero, if there's room.
+    let mut labels: Vec<Option<String>> = vec![None; height];
+    let ends = |v: f64| {
+        let end = eighth(v);
+        if end > base || (end == base && base == 0) {
+            row_of(end.max(1) - 1)
+        } else {
+            row_of(end)
+        }
+    };
+    labels[ends(highest)] = Some(axis::label(highest, precision, unit));
+    let lowest_row = ends(lowest);
+    if labels[lowest_row].is_none() {
+        labels[lowest_row] = Some(axis::label(lowest, precision, unit));
+    }
+    if crosses_zero {
+        // Zero is between rows, so it can go on the one above or below.
+        if let Some(row) = [row_of(base), row_of(base - 1)]
+            .iter()
+            .cloned()
+            .find(|&r| labels[r].is_none())
+        {
+            labels[row] = Some(axis::label(0., 0, unit));
+        }
+    }
+    let label_width = labels
+        .iter()
+        .flatten()
+        .map(|l| l.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let mut lines: Vec<String> = labels
+        .iter()
+        .enumerate()
+        .map(|(row, label)| {
+            let bottom = (height - 1 - row) * 8;
+            let cells: String = bars
+                .iter()
+                .enumerate()
+                .map(|(x, bar)| {
+                    let c = bar.map_or(' ', |(start, end)| block(start, end, bottom));
+                    match highlight {
+                        Some(h) if marked == Some(x) => h.style().paint(c.to_string()).to_string(),
+                        _ => c.to_string(),
+                    }
+                })
+                .collect();
+            axis::row(label.as_ref().map(String::as_str), label_width, &cells)
+        })
+        .collect();
+
+    let ticks: Vec<(usize, &str)> = ticks.iter().map(|(i, l)| (*i, l.as_str())).collect();
+    lines.extend(axis::x_axis(
+        label_width,
+        values.len(),
+        &ticks,
+        &flags,
+        marked,
+    ));
+
+    lines.join("\n")
+}
+
+/// The character for the part of a bar covering eighths `start` to `end` in the row starting
+/// at eighth `bottom`.
+fn block(start: usize, end: usize, bottom: usize) -> char {
+    let top = bottom + 8;
+    if end <= bottom || start >= top {
+        return ' ';
+    }
+    if start <= bottom {
+        // Filled from the bottom of the character.
+        return LOWER[end.min(top) - bottom];
+    }
+    // Filled from the top, for bars hanging down.
+    match top - start {
+        1..=2 => '▔',
+        3..=5 => '▀',
+        _ => '█',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eighths_over_rows() {
+        let values = [Some(0.), Some(4.), Some(8.), Some(16.)];
+        assert_eq!(
+            "16 ┤   █\n 0 ┤▁▄██\n   └────",
+            chart(&values, (0., 16.), "", 2, &[], None)
+        );
+    }
+
+    #[test]
+    fn around_zero() {
+        let values = [Some(-10.), Some(-5.), Some(0.), Some(5.), Some(10.)];
+        assert_eq!(
+            " 10° ┤    █\n  0° ┤  ▁██\n     │██\n-10° ┤█\n     └─────",
+            chart(&values, (-10., 10.), "°", 4, &[], None)
+        );
+    }
+
+    #[test]
+    fn all_below_zero() {
+        let values = [Some(-30.), Some(-25.), Some(-20.)];
+        let chart = chart(
+            &values,
+            (-30., -20.),
+            "°",
+            2,
+            &[(0, "Mon".to_string())],
+            None,
+        );
+        let lines: Vec<_> = chart.lines().collect();
+
+        assert_eq!("-20° ┤██▔", lines[0]);
+        assert_eq!("-30° ┤█", lines[1]);
+        assert_eq!("     └┬──", lines[2]);
+        assert_eq!("      Mon", lines[3]);
+    }
+
+    #[test]
+    fn marks_highlighted_column() {
+        let highlight = Highlight {
+            position: Some(1),
+            ..Highlight::default()
+        };
+        let chart = chart(
+            &[Some(1.), Some(2.)],
+            (1., 2.),
+            " hPa",
+            1,
+            &[],
+            Some(&highlight),
+        );
+        assert_eq!("2.0 hPa ┤▁█\n        └─▲", chart);
+    }
+
+    #[test]
+    fn zero_anchored() {
+        let values = [Some(2.), Some(4.)];
+        assert_eq!(
+            "4.0 ┤ █\n2.0 ┤██\n    └──",
+            chart(&values, (0., 4.), "", 2, &[], None)
+        );
+    }
+
+    #[test]
+    fn out_of_range_flagged() {
+        let values = [Some(-40.), Some(-5.), Some(5.)];
+        let chart = chart(&values, (-20., 0.), "°", 2, &[], None);
+        let lines: Vec<_> = chart.lines().collect();
+
+        // The first is drawn to the bottom, and the last along the top.
+        assert_eq!("  0° ┤█▀▔", lines[0], "{}", chart);
+        assert_eq!("-20° ┤█", lines[1]);
+        assert_eq!("     └↓─↑", lines[2]);
+    }
+}
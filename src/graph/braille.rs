@@ -0,0 +1,206 @@
+//! Line charts drawn with braille characters. Each character is a grid of 2×4 dots, so these
+//! show a lot more detail than a sparkline's eight levels.
+
+use super::{axis, clamp, flags, Highlight};
+
+/// The braille character with no dots; each dot adds a bit to it.
+const BLANK: u32 = 0x2800;
+
+/// The bit for each dot in a character, by column, then by row from the top.
+const DOTS: [[u32; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+
+/// Draw `values` as a line chart `width` characters wide and `height` tall, from `range`'s
+/// minimum to its maximum, which are labelled in `unit` on the y axis. `ticks` (value index and
+/// label) go on the x axis, as do arrows under values outside the range, drawn at its edge.
+///
+/// The highlighted value gets a `▲` under it, and its column is painted with the highlight's
+/// colours. Only the dots in it take the `fg`, so it only shows as a column with a `bg`.
+pub(super) fn chart(
+    values: &[Option<f64>],
+    range: (f64, f64),
+    unit: &str,
+    width: usize,
+    height: usize,
+    ticks: &[(usize, String)],
+    highlight: Option<&Highlight>,
+) -> String {
+    if width == 0 || height == 0 || values.iter().all(Option::is_none) {
+        return String::new();
+    }
+
+    let columns = width * 2;
+    let levels = height * 4;
+    let (min, max) = range;
+    let level = |v: f64| {
+        if max > min {
+            ((v - min) / (max - min) * (levels - 1) as f64).round() as usize
+        } else {
+            0
+        }
+    };
+
+    let mut cells = vec![vec![0; width]; height];
+    let mut previous: Option<usize> = None;
+    for (x, value) in resample(&clamp(values, min, max), columns)
+        .into_iter()
+        .enumerate()
+    {
+        let y = value.map(level);
+        if let Some(y) = y {
+            // Join each point to the one before it with a vertical line.
+            let (low, high) = match previous {
+                Some(p) if p < y => (p + 1, y),
+                Some(p) if p > y => (y, p - 1),
+                _ => (y, y),
+            };
+            for dot in low..=high {
+                let from_top = levels - 1 - dot;
+                cells[from_top / 4][x / 2] |= DOTS[x % 2][from_top % 4];
+            }
+        }
+        previous = y;
+    }
+
+    // Which character each value is drawn in.
+    let n = values.len();
+    let cell = |i: usize| {
+        if n > 1 {
+            (i * (columns - 1) + (n - 1) / 2) / (n - 1) / 2
+        } else {
+            0
+        }
+    };
+    let marked = highlight.and_then(|h| h.position).map(cell);
+
+    let precision = axis::precision(min, max);
+    let labels = if height == 1 {
+        // The one row gets the whole range.
+        vec![format!(
+            "{}\u{2013}{}",
+            axis::label(min, precision, ""),
+            axis::label(max, precision, unit)
+        )]
+    } else {
+        vec![
+            axis::label(max, precision, unit),
+            axis::label(min, precision, unit),
+        ]
+    };
+    let label_width = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+
+    let mut lines: Vec<String> = cells
+        .iter()
+        .enumerate()
+        .map(|(row, bits)| {
+            let label = if row == 0 {
+                labels.first()
+            } else if row == height - 1 {
+                labels.last()
+            } else {
+                None
+            };
+            let dots: String = bits
+                .iter()
+                .enumerate()
+                .map(|(x, &b)| {
+                    let c = std::char::from_u32(BLANK + b).unwrap_or(' ').to_string();
+                    match highlight {
+                        Some(h) if marked == Some(x) => h.style().paint(c).to_string(),
+                        _ => c,
+                    }
+                })
+                .collect();
+            axis::row(label.map(String::as_str), label_width, &dots)
+        })
+        .collect();
+
+    let ticks: Vec<(usize, &str)> = ticks.iter().map(|(i, l)| (cell(*i), l.as_str())).collect();
+    let flags: Vec<(usize, char)> = flags(values, min, max)
+        .into_iter()
+        .map(|(i, flag)| (cell(i), flag))
+        .collect();
+    lines.extend(axis::x_axis(label_width, width, &ticks, &flags, marked));
+
+    lines.join("\n")
+}
+
+/// Stretch or squeeze `values` to `columns` values, drawing straight lines between them.
+fn resample(values: &[Option<f64>], columns: usize) -> Vec<Option<f64>> {
+    let n = values.len();
+    (0..columns)
+        .map(|c| {
+            if n < 2 || columns < 2 {
+                return values.first().and_then(|v| *v);
+            }
+            let x = c as f64 * (n - 1) as f64 / (columns - 1) as f64;
+            let (i, t) = (x.floor() as usize, x.fract());
+            match (values[i], values.get(i + 1).and_then(|v| *v)) {
+                (Some(a), Some(b)) => Some(a + (b - a) * t),
+                (a, b) => {
+                    if t < 0.5 {
+                        a
+                    } else {
+                        b
+                    }
+                }
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rising_line() {
+        let values = [Some(0.), Some(1.), Some(2.), Some(3.)];
+        assert_eq!(
+            "0.0–3.0 ┤⡠⠊\n        └──",
+            chart(&values, (0., 3.), "", 2, 1, &[], None)
+        );
+    }
+
+    #[test]
+    fn labels_and_ticks() {
+        let values: Vec<_> = (0..8).map(|v| Some(f64::from(v) * 10.)).collect();
+        let ticks = vec![(0, "00".to_string()), (6, "06".to_string())];
+        let chart = chart(&values, (0., 70.), "", 4, 2, &ticks, None);
+        let lines: Vec<_> = chart.lines().collect();
+
+        assert_eq!(4, lines.len(), "{}", chart);
+        assert!(lines[0].starts_with("70 ┤"));
+        assert!(lines[1].starts_with(" 0 ┤"));
+        assert_eq!("   └┬──┬", lines[2]);
+        assert_eq!("    00 06", lines[3]);
+    }
+
+    #[test]
+    fn marker_column() {
+        let values = [Some(1.), None, Some(3.), Some(2.)];
+        let highlight = Highlight {
+            position: Some(2),
+            ..Highlight::default()
+        };
+        let chart = chart(&values, (1., 3.), "°", 4, 2, &[], Some(&highlight));
+        assert!(chart.lines().nth(2).unwrap().ends_with("──▲─"), "{}", chart);
+    }
+
+    #[test]
+    fn out_of_range_flagged() {
+        let values = [Some(990.), Some(1000.), Some(1010.), Some(1050.)];
+        let chart = chart(&values, (980., 1040.), " hPa", 2, 2, &[], None);
+        let lines: Vec<_> = chart.lines().collect();
+
+        assert!(lines[0].starts_with("1040 hPa ┤"), "{}", chart);
+        assert!(lines[1].starts_with(" 980 hPa ┤"));
+        assert_eq!("         └─↑", lines[2]);
+        // The last value is drawn at the top.
+        assert!(lines[0].ends_with('⡸'), "{}", chart);
+    }
+
+    #[test]
+    fn nothing_to_draw() {
+        assert_eq!("", chart(&[None, None], (0., 1.), "", 4, 2, &[], None));
+    }
+}
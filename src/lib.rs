@@ -5,277 +5,936 @@ extern crate log;
 #[macro_use]
 extern crate serde_derive;
 
+pub mod alerts;
 pub mod app;
+pub mod bar;
+mod cache;
 pub mod color;
 mod config;
 mod error;
+pub mod forecast;
+mod geocode;
 pub mod graph;
+pub mod notify;
+pub mod provider;
+mod schema;
+pub mod svg;
+pub mod table;
+mod template;
+#[cfg(test)]
+mod test_server;
 
-use chrono::{DateTime, Local, TimeZone, Timelike};
+use chrono::{DateTime, FixedOffset, Local, Offset, TimeZone, Timelike};
+use chrono_tz::Tz;
 use clap::ArgMatches;
-use darksky::models::Icon as DarkskyIcon;
-use darksky::{Block, DarkskyReqwestRequester, Language, Unit};
-use reqwest::Client;
+use serde_json::{json, Value};
+use std::env;
 use std::fs::File;
 use std::io::prelude::*;
+use std::thread;
 use weather_icons::{Condition, DripIcon, Moon, Time, WeatherIcon};
 
 pub use crate::config::Config;
+use crate::config::{ChartStyle, IconStyle};
 pub use crate::error::Error;
-use crate::graph::Graph;
+use crate::forecast::{
+    Alert, DataPoint, Daylight, Forecast, Icon, Metric, PrecipType, Severity, SymbolCode,
+    SymbolKind,
+};
+use crate::graph::{Graph, Highlight, Scale};
+use crate::provider::{Provider, Request};
+use crate::template::Template;
 
 type Result<T> = std::result::Result<T, Error>;
 
+/// Shown after the forecast when it's an old one, formatted with the time it was fetched.
+const DEFAULT_STALE_FORMAT: &str = "(as of %H:%M)";
+
+/// Polybar's `font-1`, the first font after the bar's main one.
+const DEFAULT_BAR_FONT: usize = 2;
+
+/// How many rows tall charts are without `chart_height`.
+const DEFAULT_CHART_HEIGHT: usize = 4;
+
+/// How many characters wide each day is in a chart of the daily forecast, enough for its name.
+const CHART_DAY_WIDTH: usize = 4;
+
+/// What the weather is formatted for.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Mode {
+    Plain,
+    I3blocks,
+    Waybar,
+    Polybar,
+    Tmux,
+}
+
+impl Mode {
+    fn from_matches(matches: &ArgMatches<'_>) -> Self {
+        if matches.is_present("i3") {
+            Mode::I3blocks
+        } else if matches.is_present("waybar") {
+            Mode::Waybar
+        } else if matches.is_present("polybar") {
+            Mode::Polybar
+        } else if matches.is_present("tmux") {
+            Mode::Tmux
+        } else {
+            Mode::Plain
+        }
+    }
+
+    /// Waybar understands the same pango markup as i3blocks.
+    fn is_pango(self) -> bool {
+        self == Mode::I3blocks || self == Mode::Waybar
+    }
+}
+
+/// Where to get the weather for.
+#[derive(Debug, Default)]
+struct Site {
+    lat: f64,
+    lon: f64,
+    /// Its name in the `[locations]` section of the config file, if it's from there.
+    location: Option<String>,
+    /// What to call it in the output, if it has a name.
+    name: Option<String>,
+    /// Its time zone, for providers that don't report one.
+    timezone: Option<Tz>,
+}
+
 pub fn run(config: &Config, matches: &ArgMatches<'_>) -> Result<()> {
-    let weather_data = get_weather(&config, &matches)?;
-    if matches.occurrences_of("json") == 1 {
-        println!(
-            "{}",
-            serde_json::to_string_pretty(&weather_data)
-                .expect("couldn't convert weather data back to json")
-        );
+    let responses = get_responses(config, matches)?;
+
+    if matches.is_present("raw") {
+        let outputs = responses
+            .into_iter()
+            .map(|(site, r)| (site.location.unwrap_or_default(), r.map(|(v, _)| v)))
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&keyed(outputs)?)?);
+        return Ok(());
+    }
+
+    let forecasts = parse_responses(config, responses);
+
+    if matches.value_of("format") == Some("json") {
+        let outputs = forecasts
+            .into_iter()
+            .map(|(site, r)| {
+                let output = r.and_then(|(weather, _)| {
+                    Ok(serde_json::to_value(schema::Output::new(&weather))?)
+                });
+                (site.location.unwrap_or_default(), output)
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&keyed(outputs)?)?);
     } else {
-        print_weather(matches, config, weather_data)?;
+        let mode = Mode::from_matches(matches);
+        let long = matches.is_present("long");
+        println!("{}", format_sites(config, mode, long, forecasts)?);
     }
 
     Ok(())
 }
 
-fn get_weather(config: &Config, matches: &ArgMatches<'_>) -> Result<darksky::models::Forecast> {
-    if matches.is_present("debug") || matches.is_present("local") {
-        let mut contents = String::new();
+/// The output for each site, keyed by its name in the config file when there are several. A
+/// site whose forecast couldn't be had gets its error instead, unless they all failed.
+fn keyed(mut outputs: Vec<(String, Result<Value>)>) -> Result<Value> {
+    if outputs.iter().all(|(_, o)| o.is_err()) || outputs.len() == 1 {
+        return outputs
+            .pop()
+            .map_or_else(|| Ok(Value::Null), |(_, output)| output);
+    }
 
-        let path = if let Some(p) = matches.value_of("local") {
-            p.to_string()
-        } else {
-            config
-                .local
-                .clone()
-                .expect("couldn't get the local path from the config")
-        };
+    Ok(Value::Object(
+        outputs
+            .into_iter()
+            .map(|(name, output)| {
+                let output = output.unwrap_or_else(|e| json!({ "error": e.to_string() }));
+                (name, output)
+            })
+            .collect(),
+    ))
+}
 
-        info!("using local file: {}", path);
-        let mut f = File::open(path)?;
-        f.read_to_string(&mut contents)?;
-        serde_json::from_str(&contents).map_err(Error::Json)
-    } else {
-        let client = Client::new();
-
-        let get_options = |o: darksky::Options| -> darksky::Options {
-            let o = o
-                .exclude(vec![Block::Minutely])
-                .unit(Unit::Ca)
-                .language(Language::En);
-            if matches.is_present("extend_hourly") {
-                o.extend_hourly()
-            } else {
-                o
+/// The weather at each site, one after the other. A site whose forecast couldn't be had is
+/// shown with its error, so that the others are still shown. It's only an error if they all
+/// failed.
+fn format_sites(
+    config: &Config,
+    mode: Mode,
+    long: bool,
+    forecasts: Vec<SiteForecast>,
+) -> Result<String> {
+    if forecasts.iter().all(|(_, f)| f.is_err()) {
+        if let Some((_, Err(e))) = forecasts.into_iter().next() {
+            return Err(e);
+        }
+        return Ok(String::new());
+    }
+
+    let several = forecasts.len() > 1;
+    let mut outputs = Vec::new();
+
+    for (site, forecast) in forecasts {
+        let (weather, stale_since) = match forecast {
+            Ok(f) => f,
+            Err(e) => {
+                let name = site.name.or(site.location).unwrap_or_default();
+                outputs.push(if mode == Mode::Waybar {
+                    json!({
+                        "text": format!("{}: \u{26a0}", name),
+                        "tooltip": e.to_string(),
+                        "class": ["error"],
+                    })
+                    .to_string()
+                } else {
+                    format!("{}: {}", name, e)
+                });
+                continue;
             }
         };
 
-        // Call the appropriate API if showing historical weather.
-        if matches.occurrences_of("historical") == 0 {
-            client
-                .get_forecast_with_options(
-                    &config.token,
-                    if matches.is_present("latitude") {
-                        value_t!(matches.value_of("latitude"), f64)?
-                    } else {
-                        config.lat
-                    },
-                    if matches.is_present("longitude") {
-                        value_t!(matches.value_of("longitude"), f64)?
-                    } else {
-                        config.lon
-                    },
-                    get_options,
-                )
-                .map_err(Error::Darksky)
+        let name = weather.place.clone();
+        let time = Local.timestamp(weather.current.time, 0);
+        let output = format_weather(config, weather, mode, long, time, stale_since)?;
+        outputs.push(match name {
+            Some(ref name) if several && !long && mode != Mode::Waybar => {
+                format!("{}: {}", name, output)
+            }
+            _ => output,
+        });
+    }
+
+    let separator = if mode == Mode::I3blocks || mode == Mode::Tmux {
+        // These only show the first line.
+        " "
+    } else if long {
+        "\n\n"
+    } else {
+        "\n"
+    };
+    Ok(outputs.join(separator))
+}
+
+fn is_local(matches: &ArgMatches<'_>) -> bool {
+    matches.is_present("debug") || matches.is_present("local")
+}
+
+/// Every location given with `--location`, or else the one from `get_site`.
+fn get_sites(config: &Config, matches: &ArgMatches<'_>) -> Result<Vec<Site>> {
+    match matches.values_of("location") {
+        Some(names) if matches.occurrences_of("latitude") == 0 => {
+            // A location given twice is only shown once.
+            let mut seen = Vec::new();
+            names
+                .filter(|name| {
+                    let new = !seen.contains(name);
+                    seen.push(*name);
+                    new
+                })
+                .map(|name| get_saved_site(config, name))
+                .collect()
+        }
+        _ => Ok(vec![get_site(config, matches)?]),
+    }
+}
+
+/// Work out where to get the weather for. In order, this is the first of: coordinates on the
+/// command line, `--place`, `--location`, coordinates from the environment,
+/// `default_location`, and coordinates in the config file.
+fn get_site(config: &Config, matches: &ArgMatches<'_>) -> Result<Site> {
+    if matches.occurrences_of("latitude") > 0 {
+        return get_site_from_args(config, matches);
+    }
+
+    if let Some(query) = matches.value_of("place") {
+        let place = geocode::from_config(config).lookup(query)?;
+        return Ok(Site {
+            lat: place.lat,
+            lon: place.lon,
+            name: Some(place.name),
+            ..Site::default()
+        });
+    }
+
+    if let Some(name) = matches.value_of("location") {
+        return get_saved_site(config, name);
+    }
+
+    if matches.is_present("latitude") {
+        return get_site_from_args(config, matches);
+    }
+
+    if let Some(ref name) = config.default_location {
+        return get_saved_site(config, name);
+    }
+
+    get_site_from_args(config, matches)
+}
+
+/// Coordinates from the `latitude` and `longitude` arguments, falling back to the config file.
+fn get_site_from_args(config: &Config, matches: &ArgMatches<'_>) -> Result<Site> {
+    Ok(Site {
+        lat: if matches.is_present("latitude") {
+            value_t!(matches.value_of("latitude"), f64)?
         } else {
-            client
-                .get_forecast_time_machine(
-                    &config.token,
-                    config.lat,
-                    config.lon,
-                    matches
-                        .value_of("historical")
-                        .expect("couldn't read argument to historical option"),
-                    get_options,
-                )
-                .map_err(Error::Darksky)
+            config.lat.ok_or(Error::MissingLocation)?
+        },
+        lon: if matches.is_present("longitude") {
+            value_t!(matches.value_of("longitude"), f64)?
+        } else {
+            config.lon.ok_or(Error::MissingLocation)?
+        },
+        ..Site::default()
+    })
+}
+
+/// A location from the `[locations]` section of the config file.
+fn get_saved_site(config: &Config, name: &str) -> Result<Site> {
+    let location = config
+        .locations
+        .get(name)
+        .ok_or_else(|| Error::UnknownLocation(name.to_string()))?;
+
+    let (lat, lon) = match (location.lat, location.lon, &location.place) {
+        (Some(lat), Some(lon), _) => (lat, lon),
+        (_, _, Some(place)) => {
+            let place = geocode::from_config(config).lookup(place)?;
+            (place.lat, place.lon)
+        }
+        _ => return Err(Error::InvalidLocation(name.to_string())),
+    };
+
+    Ok(Site {
+        lat,
+        lon,
+        location: Some(name.to_string()),
+        name: Some(location.label.clone().unwrap_or_else(|| name.to_string())),
+        timezone: match location.timezone {
+            Some(ref tz) => Some(
+                tz.parse()
+                    .map_err(|_| Error::UnknownTimezone(tz.to_string()))?,
+            ),
+            None => None,
+        },
+    })
+}
+
+/// Get the provider's response for each site, and when it was fetched if it's an old one used
+/// because the provider couldn't be reached.
+///
+/// Sites are fetched at the same time, so this takes about as long as the slowest one.
+fn get_weather(
+    config: &Config,
+    matches: &ArgMatches<'_>,
+    sites: &[Site],
+) -> Vec<Result<(Value, Option<i64>)>> {
+    if is_local(matches) {
+        return vec![read_local(config, matches).map(|v| (v, None))];
+    }
+
+    let ttl = config.cache_ttl.unwrap_or(cache::DEFAULT_TTL);
+    let refresh = matches.is_present("refresh");
+    let time = if matches.occurrences_of("historical") == 0 {
+        None
+    } else {
+        Some(
+            matches
+                .value_of("historical")
+                .expect("couldn't read argument to historical option")
+                .to_string(),
+        )
+    };
+
+    let handles: Vec<_> = sites
+        .iter()
+        .map(|site| {
+            let provider = provider::from_config(config);
+            let request = Request {
+                lat: site.lat,
+                lon: site.lon,
+                extend_hourly: extend_hourly(matches),
+                time: time.clone(),
+            };
+            thread::spawn(move || fetch(&*provider, ttl, refresh, &request))
+        })
+        .collect();
+
+    handles
+        .into_iter()
+        .map(|handle| handle.join().expect("forecast thread panicked"))
+        .collect()
+}
+
+/// The response saved in the file given with `--local`, or `local` in the config file.
+fn read_local(config: &Config, matches: &ArgMatches<'_>) -> Result<Value> {
+    let mut contents = String::new();
+
+    let path = if let Some(p) = matches.value_of("local") {
+        p.to_string()
+    } else {
+        config
+            .local
+            .clone()
+            .expect("couldn't get the local path from the config")
+    };
+
+    info!("using local file: {}", path);
+    let mut f = File::open(path)?;
+    f.read_to_string(&mut contents)?;
+    serde_json::from_str(&contents).map_err(Error::Json)
+}
+
+/// Whether to ask for a week of hourly data, with `--extend` either before or after `hourly`.
+fn extend_hourly(matches: &ArgMatches<'_>) -> bool {
+    match matches.subcommand_matches("hourly") {
+        Some(m) if m.is_present("extend_hourly") => true,
+        _ => matches.is_present("extend_hourly"),
+    }
+}
+
+/// The forecast for each site, by its name in the config file, and when it was fetched if it's
+/// old.
+type Forecasts = Vec<(String, Forecast, Option<i64>)>;
+
+/// A site and the provider's response for it, and when it was fetched if it's old, or why it
+/// couldn't be had.
+type SiteResponse = (Site, Result<(Value, Option<i64>)>);
+
+/// A site and its forecast, and when it was fetched if it's old, or why it couldn't be had.
+type SiteForecast = (Site, Result<(Forecast, Option<i64>)>);
+
+/// Fetch and parse the forecast for every site.
+fn get_forecasts(config: &Config, matches: &ArgMatches<'_>) -> Result<Forecasts> {
+    parse_responses(config, get_responses(config, matches)?)
+        .into_iter()
+        .map(|(site, forecast)| {
+            let (weather, stale_since) = forecast?;
+            Ok((site.location.unwrap_or_default(), weather, stale_since))
+        })
+        .collect()
+}
+
+/// Fetch the provider's response for every site.
+fn get_responses(config: &Config, matches: &ArgMatches<'_>) -> Result<Vec<SiteResponse>> {
+    let sites = if is_local(matches) {
+        vec![Site::default()]
+    } else {
+        get_sites(config, matches)?
+    };
+    let responses = get_weather(config, matches, &sites);
+
+    Ok(sites.into_iter().zip(responses).collect())
+}
+
+/// Parse each site's response, naming the forecast after the site and placing it in the site's
+/// time zone if the provider didn't say.
+fn parse_responses(config: &Config, responses: Vec<SiteResponse>) -> Vec<SiteForecast> {
+    let provider = provider::from_config(config);
+
+    responses
+        .into_iter()
+        .map(|(site, response)| {
+            let forecast = response.and_then(|(weather_data, stale_since)| {
+                let mut weather = provider.parse_in(weather_data, site.timezone)?;
+                weather.place = site.name.clone();
+                Ok((weather, stale_since))
+            });
+            (site, forecast)
+        })
+        .collect()
+}
+
+/// Fetch a forecast through the cache, if there is one.
+fn fetch(
+    provider: &dyn Provider,
+    ttl: u64,
+    refresh: bool,
+    request: &Request,
+) -> Result<(Value, Option<i64>)> {
+    match cache::dir() {
+        Some(dir) => {
+            let entry = cache::fetch(&dir, ttl, refresh, provider, request)?;
+            let stale_since = if entry.stale {
+                Some(entry.fetched)
+            } else {
+                None
+            };
+            Ok((entry.response, stale_since))
         }
+        None => Ok((provider.fetch(request)?, None)),
     }
 }
 
 pub fn print_weather(
     matches: &ArgMatches<'_>,
     config: &Config,
-    weather: darksky::models::Forecast,
+    weather: Forecast,
+    stale_since: Option<i64>,
 ) -> Result<()> {
-    let c = weather.currently.expect("current weather missing");
-    let d = weather.daily.expect("daily weather forecast missing");
-    let h = weather.hourly.expect("hourly weather forecast missing");
-
-    let hourly_data = h.data.expect("hourly data missing");
-    let daily_data = d.data.expect("daily data missing");
-
-    let degrees = "°";
-
-    let mut output = format!(
-        "{current_temp}{degrees} {summary}. ({feels_like_temp}{degrees})",
-        degrees = degrees,
-        current_temp = c.temperature.expect("current temperature missing").round(),
-        summary = c.summary.clone().expect("current summary missing"),
-        feels_like_temp = c
-            .apparent_temperature
-            .expect("current apparent temperature missing")
-            .round()
+    let time = Local.timestamp(weather.current.time, 0);
+    println!(
+        "{}",
+        format_weather(
+            config,
+            weather,
+            Mode::from_matches(matches),
+            matches.is_present("long"),
+            time,
+            stale_since
+        )?
     );
+    Ok(())
+}
+
+/// Format the weather, with the hour closest to `time` highlighted on the graph.
+fn format_weather(
+    config: &Config,
+    weather: Forecast,
+    mode: Mode,
+    long: bool,
+    time: DateTime<Local>,
+    stale_since: Option<i64>,
+) -> Result<String> {
+    let c = &weather.current;
+    let d = &weather.daily;
+    let h = &weather.hourly;
 
-    let pressures: Vec<Option<f64>> = hourly_data.iter().map(|d| d.pressure).collect();
+    let hourly_data = &h.data;
+    let daily_data = &d.data;
+
+    let hourly_graphs: Vec<(Metric, Vec<Option<f64>>)> = config
+        .graphs
+        .clone()
+        .unwrap_or_else(|| vec![Metric::Pressure])
+        .into_iter()
+        .map(|m| (m, m.values(hourly_data)))
+        .collect();
+    let daily_graphs: Vec<(Metric, Vec<Option<f64>>)> = config
+        .daily_graphs
+        .clone()
+        .unwrap_or_else(|| vec![Metric::TemperatureHigh])
+        .into_iter()
+        .map(|m| (m, m.values(daily_data)))
+        .collect();
+    debug!("hourly graphs: {:?}", hourly_graphs);
+    debug!("daily graphs: {:?}", daily_graphs);
 
     let times: Vec<DateTime<Local>> = hourly_data
         .iter()
-        .map(|d| Local.timestamp(d.time as i64, 0))
+        .map(|d| Local.timestamp(d.time, 0))
         .collect();
 
-    let mut pressure_graph = Graph::new();
-    pressure_graph.values(&pressures);
-
-    let position = find_closest_time_position(&Local.timestamp(c.time as i64, 0), &times);
+    let position = find_closest_time_position(&time, &times);
     info!("calculated position: {:?}", position);
 
-    if let Some(ref h) = config.highlight {
-        pressure_graph.highlight(&position, h);
-    }
+    let mut font = Graph::new().font;
 
     if let Some(ref f) = config.font_style {
         info!("{:?}", f);
-        pressure_graph.font.style = *f;
+        font.style = *f;
     }
 
     if let Some(ref w) = config.font_weight {
-        pressure_graph.font.weight = *w;
+        font.weight = *w;
     }
 
-    debug!("pressure graph: {:?}", pressure_graph);
-    debug!("pressure graph sparkline: {:?}", pressure_graph.sparkline());
-    debug!("pressure graph sparkfont: {:?}", pressure_graph.sparkfont());
+    let pango = mode.is_pango();
+
+    // Polybar and lemonbar pick fonts by number instead of by name, and fall back to whichever
+    // font has the glyph, so icons are left as-is.
+    let bar = mode == Mode::Polybar;
+    let icon = |font: &str, glyph: String| {
+        if pango {
+            format!("<span font_desc='{}'>{}</span>", font, glyph)
+        } else {
+            glyph
+        }
+    };
 
-    let daily_temperatures: Vec<Option<f64>> =
-        daily_data.iter().map(|d| d.temperature_high).collect();
+    let truecolor = config.truecolor.unwrap_or_else(|| {
+        env::var("COLORTERM")
+            .map(|c| c == "truecolor" || c == "24bit")
+            .unwrap_or(false)
+    });
 
-    let daily_temperature_spark_graph = Graph::new().values(&daily_temperatures).sparkline();
+    // Draw a graph the way the output mode can show it.
+    let draw = |name: &str, values: &[Option<f64>], position: Option<usize>| {
+        let mut graph = Graph::new();
+        graph
+            .values(values)
+            .scale(get_scale(config, name, &weather));
+        graph.font = font;
+        if let Some(ref h) = config.highlight {
+            graph.highlight(&position, h);
+        }
 
-    let (sunrise, sunset) = (
-        Local.timestamp(
-            daily_data[0].sunrise_time.expect("sunrise time missing") as i64,
-            0,
-        ),
-        Local.timestamp(
-            daily_data[0].sunset_time.expect("sunset time missing") as i64,
-            0,
-        ),
-    );
+        match mode {
+            Mode::Plain => graph.sparkline(),
+            Mode::I3blocks | Mode::Waybar => graph.sparkfont(),
+            Mode::Polybar => graph.sparkbar(config.bar_font.unwrap_or(DEFAULT_BAR_FONT)),
+            Mode::Tmux => graph.tmux_sparkline(truecolor),
+        }
+    };
 
-    if matches.is_present("i3") {
-        let pressure_icon = format!(
-            "<span font_desc='Weather Icons'>{}</span>",
-            WeatherIcon::Barometer
-        );
+    let stale = stale_since.map(|fetched| {
+        let marker = Local
+            .timestamp(fetched, 0)
+            .format(
+                config
+                    .stale_format
+                    .as_ref()
+                    .map_or(DEFAULT_STALE_FORMAT, String::as_str),
+            )
+            .to_string();
 
-        let current_condition_icon = format!(
-            //"<span font_desc='Weather Icons'>{icon}</span>",
-            "<span font_desc='dripicons-weather'>{icon}</span>",
-            icon = DripIcon::from(get_current_condition_icon(
-                //icon = get_current_condition_icon(
-                c.icon.expect("current icon missing"),
-                &Local::now(),
-                &sunrise,
-                &sunset
+        if pango {
+            format!("<span alpha='50%'>{}</span>", marker)
+        } else if mode == Mode::Tmux {
+            format!("#[dim]{}#[nodim]", marker)
+        } else {
+            marker
+        }
+    });
+
+    let alerts = weather.active_alerts();
+    let alert_marker = if alerts.is_empty() {
+        None
+    } else {
+        let marker = format!("\u{26a0} {}", alerts.len());
+        if pango {
+            let severity = alerts
+                .iter()
+                .filter_map(|a| a.severity)
+                .max()
+                .unwrap_or(Severity::Advisory);
+            Some(format!(
+                "<span color='{}'>{}</span>",
+                get_alert_color(severity),
+                marker
             ))
-        );
+        } else {
+            Some(marker)
+        }
+    };
 
-        let wind_bearing_icon = get_wind_bearing_icon(
-            c.wind_bearing
-                .expect("current wind bearing missing")
-                .trunc() as u32,
-        );
+    let template = get_template(config, mode)
+        .map(|t| t.parse::<Template>())
+        .transpose()?;
 
-        let moon = format!(
-            "<span font_desc='Weather Icons'>{}</span>",
-            Moon::new(
-                daily_data[0]
-                    .moon_phase
-                    .expect("first day moon phase missing"),
-                config.moon_style
-            )?
+    let today = daily_data.first();
+    let sunrise = today
+        .and_then(|d| d.sunrise_time)
+        .map(|t| Local.timestamp(t, 0));
+    let sunset = today
+        .and_then(|d| d.sunset_time)
+        .map(|t| Local.timestamp(t, 0));
+    let condition_icon = match (sunrise, sunset) {
+        (Some(sunrise), Some(sunset)) => get_current_condition_icon(c, &time, &sunrise, &sunset),
+        // Assume it's daytime.
+        _ => get_current_condition_icon(c, &time, &time, &time),
+    };
+    let moon = match today.and_then(|d| d.moon_phase) {
+        Some(phase) => icon(
+            "Weather Icons",
+            Moon::new(phase, config.moon_style)?.to_string(),
+        ),
+        None => String::new(),
+    };
+    let wind_arrow = c
+        .wind_bearing
+        .map(|b| get_wind_bearing_icon(b.trunc() as u32).to_string())
+        .unwrap_or_default();
+
+    let mut output = if let Some(ref template) = template {
+        let context = template::Context {
+            weather: &weather,
+            icon: match (condition_icon, config.icon_style.unwrap_or_default()) {
+                (None, _) => String::new(),
+                (Some(i), IconStyle::WeatherIcons) => icon("Weather Icons", i.to_string()),
+                (Some(i), IconStyle::Dripicons) => {
+                    icon("dripicons-weather", DripIcon::from(i).to_string())
+                }
+            },
+            moon: moon.clone(),
+            wind_arrow: wind_arrow.clone(),
+            pressure_icon: icon("Weather Icons", WeatherIcon::Barometer.to_string()),
+            stale: stale.clone().unwrap_or_default(),
+            alerts: alert_marker.clone().unwrap_or_default(),
+            graph: &draw,
+            position,
+            escape: if mode == Mode::Tmux {
+                // tmux would read a `#` in the summary as the start of a format.
+                |text| text.replace('#', "##")
+            } else {
+                |text| text
+            },
+        };
+        template.render(&context)
+    } else {
+        let degrees = "°";
+        // Not every provider gives every value, so a missing one is shown as a `?`.
+        let rounded = |value: Option<f64>| value.map_or("?".to_string(), |v| v.round().to_string());
+
+        let mut output = format!(
+            "{current_temp}{degrees} {summary}. ({feels_like_temp}{degrees})",
+            degrees = degrees,
+            current_temp = rounded(c.temperature),
+            summary = c.summary.as_ref().map_or("?", String::as_str),
+            feels_like_temp = rounded(c.apparent_temperature),
         );
 
-        output = [
-            pressure_icon,
-            pressure_graph.sparkfont(),
-            current_condition_icon,
-            output,
+        if pango || bar {
+            // Only pressure has an icon to go with its graph.
+            let graphs = hourly_graphs
+                .iter()
+                .map(|(metric, values)| match metric {
+                    Metric::Pressure => format!(
+                        "{} {}",
+                        icon("Weather Icons", WeatherIcon::Barometer.to_string()),
+                        draw(metric.name(), values, position)
+                    ),
+                    _ => draw(metric.name(), values, position),
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            let current_condition_icon = condition_icon
+                .map(|i| icon("dripicons-weather", DripIcon::from(i).to_string()))
+                .unwrap_or_else(|| "?".to_string());
+
+            output = [
+                graphs,
+                current_condition_icon,
+                output,
+                icon("dripicons-weather", DripIcon::Flag.to_string()),
+                wind_arrow,
+                format!("{} km/h", rounded(c.wind_speed)),
+                moon,
+            ]
+            .join(" ");
+        }
+
+        if mode == Mode::Tmux {
+            // tmux would read a `#` in the summary as the start of a format.
+            let graphs = hourly_graphs
+                .iter()
+                .map(|(metric, values)| draw(metric.name(), values, position))
+                .collect::<Vec<_>>()
+                .join(" ");
+            output = format!("{} {}", output.replace('#', "##"), graphs);
+        }
+
+        output
+    };
+
+    if let Some(marker) = stale {
+        match template {
+            Some(ref t) if t.shows_stale() => {}
+            _ => output = format!("{} {}", output, marker),
+        }
+    }
+
+    // Other modes only show alerts where a template asks for them.
+    if let Some(marker) = alert_marker {
+        match template {
+            Some(ref t) if t.shows_alerts() => {}
+            _ if pango => output = format!("{} {}", output, marker),
+            _ => {}
+        }
+    }
+
+    // The highlight is drawn with terminal colours, which only make sense in plain output.
+    let highlight = config.highlight.as_ref().filter(|_| mode == Mode::Plain);
+    let sparkline = |metric: Metric, values: &[Option<f64>], daily: bool, position| {
+        let data = if daily { daily_data } else { hourly_data };
+        if config.chart_height.is_some() {
+            return draw_chart(config, &weather, metric, data, daily, position, highlight);
+        }
+        let mut graph = Graph::new();
+        graph
+            .values(values)
+            .scale(get_scale(config, metric.name(), &weather));
+        if let Some(h) = highlight {
+            graph.highlight(&position, h);
+        }
+        graph.sparkline()
+    };
+
+    let mut details: Vec<String> = hourly_graphs
+        .iter()
+        .map(|(metric, values)| {
             format!(
-                "<span font_desc='dripicons-weather'>{}</span>",
-                DripIcon::Flag
-            ),
-            wind_bearing_icon.to_owned(),
+                "hourly {} forecast:\n{}",
+                metric.description(),
+                sparkline(*metric, values, false, position)
+            )
+        })
+        .chain(daily_graphs.iter().map(|(metric, values)| {
             format!(
-                "{} km/h",
-                c.wind_speed.expect("current wind speed missing").round() as i32
+                "daily {} forecast:\n{}",
+                metric.description(),
+                sparkline(*metric, values, true, None)
+            )
+        }))
+        .collect();
+    details.push(
+        h.summary
+            .clone()
+            .unwrap_or_else(|| "no hourly summary".to_owned()),
+    );
+    details.push(
+        d.summary
+            .clone()
+            .unwrap_or_else(|| "no daily summary".to_owned()),
+    );
+    details.extend(
+        alerts
+            .iter()
+            .map(|a| format_alert(a, weather.utc_offset, false)),
+    );
+
+    if mode == Mode::Waybar {
+        let tooltip = weather
+            .place
+            .iter()
+            .chain(details.iter())
+            .map(|line| escape_markup(line))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let mut class: Vec<_> = c.icon.map(Icon::name).into_iter().collect();
+        if stale_since.is_some() {
+            class.push("stale");
+        }
+
+        output = json!({
+            "text": output,
+            "tooltip": tooltip,
+            "class": class,
+            "percentage": (c.precip_probability.unwrap_or_default() * 100.).round() as u8,
+        })
+        .to_string();
+    } else if long {
+        output = weather
+            .place
+            .clone()
+            .into_iter()
+            .chain(Some(output))
+            .chain(details.iter().cloned())
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+
+    Ok(output)
+}
+
+/// An alert's title, when it expires, and its description, with times at the location's UTC
+/// `offset`. `full` also gives its severity, when it was issued, the regions it covers and a
+/// link to it, for `weather alerts`.
+fn format_alert(alert: &Alert, offset: Option<i32>, full: bool) -> String {
+    let time = |t: i64, format: &str| local_time(t, offset).format(format).to_string();
+
+    let mut lines = vec![match (full, alert.severity, alert.expires) {
+        (true, Some(severity), _) => format!("\u{26a0} {} ({})", alert.title, severity.name()),
+        (false, _, Some(expires)) => format!(
+            "\u{26a0} {} (until {})",
+            alert.title,
+            time(expires, "%a %H:%M")
+        ),
+        _ => format!("\u{26a0} {}", alert.title),
+    }];
+    if full {
+        let issued = time(alert.time, "%a %b %e %H:%M");
+        lines.push(match alert.expires {
+            Some(expires) => format!(
+                "issued {}, until {}",
+                issued,
+                time(expires, "%a %b %e %H:%M")
             ),
-            moon,
-        ]
-        .join(" ");
+            None => format!("issued {}", issued),
+        });
+        if !alert.regions.is_empty() {
+            lines.push(format!("for {}", alert.regions.join(", ")));
+        }
+        lines.push(String::new());
+    }
+    lines.push(alert.description.trim().to_string());
+    if let Some(uri) = alert.uri.as_ref().filter(|_| full) {
+        lines.push(uri.clone());
     }
 
-    println!("{}", output);
+    lines.join("\n")
+}
 
-    if matches.is_present("long") {
-        println!("hourly pressure forecast:\n{}", pressure_graph.sparkline());
-        println!("temperatures this week:\n{}", daily_temperature_spark_graph);
-        println!(
-            "{}",
-            h.summary.unwrap_or_else(|| "no hourly summary".to_owned())
-        );
-        println!(
-            "{}",
-            d.summary.unwrap_or_else(|| "no daily summary".to_owned())
-        );
+/// The colour of the alerts marker, by the most severe alert.
+fn get_alert_color(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Advisory => "#f0c674",
+        Severity::Watch => "#de935f",
+        Severity::Warning => "#cc6666",
     }
+}
 
-    Ok(())
+/// The template for the output mode, if there is one.
+fn get_template(config: &Config, mode: Mode) -> Option<&String> {
+    let formats = &config.formats;
+    match mode {
+        Mode::Plain => formats.plain.as_ref(),
+        Mode::I3blocks => formats.i3blocks.as_ref(),
+        Mode::Waybar => formats.waybar.as_ref(),
+        Mode::Polybar => formats.polybar.as_ref(),
+        Mode::Tmux => formats.tmux.as_ref(),
+    }
+    .or(config.format.as_ref())
 }
 
+/// Escape text so that pango shows it as-is.
+fn escape_markup(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// The icon for a data point, by MET Norway's symbol code if it has one, and otherwise by its
+/// icon, for day or night depending on whether `now` is between sunrise and sunset.
 fn get_current_condition_icon(
-    icon: DarkskyIcon,
+    point: &DataPoint,
     now: &DateTime<Local>,
     sunrise: &DateTime<Local>,
     sunset: &DateTime<Local>,
-) -> WeatherIcon {
+) -> Option<WeatherIcon> {
     let time = if *now >= *sunrise && *now <= *sunset {
         Time::Day
     } else {
         Time::Night
     };
 
+    match point
+        .symbol_code
+        .as_ref()
+        .and_then(|s| get_symbol_code_condition(s))
+    {
+        Some((condition, t)) => Some(condition.variant(t.unwrap_or(time)).unwrap_or_default()),
+        None => point.icon.map(|i| get_condition_icon(i, time)),
+    }
+}
+
+fn get_condition_icon(icon: Icon, time: Time) -> WeatherIcon {
     let new_icon = match icon {
-        DarkskyIcon::Tornado => Some(WeatherIcon::Tornado),
-        DarkskyIcon::Wind => Some(WeatherIcon::Windy),
+        Icon::Tornado => Some(WeatherIcon::Tornado),
+        Icon::Wind => Some(WeatherIcon::Windy),
         _ => match icon {
-            DarkskyIcon::ClearNight | DarkskyIcon::ClearDay => Condition::Fair,
-            DarkskyIcon::Cloudy => Condition::Cloudy,
-            DarkskyIcon::Fog => Condition::Fog,
-            DarkskyIcon::Hail => Condition::Hail,
-            DarkskyIcon::PartlyCloudyNight | DarkskyIcon::PartlyCloudyDay => {
-                Condition::PartlyCloudy
-            }
-            DarkskyIcon::Rain => Condition::Rain,
-            DarkskyIcon::Sleet => Condition::Sleet,
-            DarkskyIcon::Snow => Condition::Snow,
-            DarkskyIcon::Thunderstorm => Condition::Thunderstorm,
+            Icon::ClearNight | Icon::ClearDay => Condition::Fair,
+            Icon::Cloudy => Condition::Cloudy,
+            Icon::Fog => Condition::Fog,
+            Icon::Hail => Condition::Hail,
+            Icon::PartlyCloudyNight | Icon::PartlyCloudyDay => Condition::PartlyCloudy,
+            Icon::Rain => Condition::Rain,
+            Icon::Sleet => Condition::Sleet,
+            Icon::Snow => Condition::Snow,
+            Icon::Thunderstorm => Condition::Thunderstorm,
             _ => unreachable!(),
         }
         .variant(time),
@@ -284,7 +943,107 @@ fn get_current_condition_icon(
     new_icon.unwrap_or_default()
 }
 
-fn find_closest_time_position(time: &DateTime<Local>, times: &[DateTime<Local>]) -> Option<usize> {
+/// The condition for a MET Norway symbol code, e.g. `lightrainshowers_day`, and the time of day
+/// if the code says. This is the MET Norway sibling of `get_condition_icon`.
+///
+/// Polar twilight is drawn as day, since the sky is lit even though the sun hasn't risen. The
+/// conditions don't have light, heavy or shower variants, so those are left to the summary.
+fn get_symbol_code_condition(symbol_code: &str) -> Option<(Condition, Option<Time>)> {
+    let symbol = SymbolCode::parse(symbol_code)?;
+    let time = symbol.daylight.map(|d| match d {
+        Daylight::Day | Daylight::PolarTwilight => Time::Day,
+        Daylight::Night => Time::Night,
+    });
+
+    let condition = match symbol.kind {
+        SymbolKind::Clear => Condition::Fair,
+        SymbolKind::Fair | SymbolKind::PartlyCloudy => Condition::PartlyCloudy,
+        SymbolKind::Cloudy => Condition::Cloudy,
+        SymbolKind::Fog => Condition::Fog,
+        SymbolKind::Precipitation(_) if symbol.thunder => Condition::Thunderstorm,
+        SymbolKind::Precipitation(PrecipType::Sleet) => Condition::Sleet,
+        SymbolKind::Precipitation(PrecipType::Snow) => Condition::Snow,
+        SymbolKind::Precipitation(PrecipType::Rain) => Condition::Rain,
+    };
+
+    Some((condition, time))
+}
+
+/// A chart of `metric` over the hourly or `daily` forecast `data`, from `weather`, in the
+/// configured style, scale and height, with a tick every six hours or every day.
+fn draw_chart(
+    config: &Config,
+    weather: &Forecast,
+    metric: Metric,
+    data: &[DataPoint],
+    daily: bool,
+    position: Option<usize>,
+    highlight: Option<&Highlight>,
+) -> String {
+    let height = config.chart_height.unwrap_or(DEFAULT_CHART_HEIGHT);
+    let ticks: Vec<(usize, String)> = data
+        .iter()
+        .enumerate()
+        .filter_map(|(i, d)| {
+            let time = local_time(d.time, weather.utc_offset);
+            match (daily, time.hour() % 6) {
+                (true, _) => Some((i, time.format("%a").to_string())),
+                (false, 0) => Some((i, time.format("%H").to_string())),
+                _ => None,
+            }
+        })
+        .collect();
+
+    let mut graph = Graph::new();
+    graph
+        .values(&metric.values(data))
+        .ticks(&ticks)
+        .unit(metric.unit())
+        .scale(get_scale(config, metric.name(), weather));
+    if let Some(h) = highlight {
+        graph.highlight(&position, h);
+    }
+
+    match config.chart_style.unwrap_or_default() {
+        ChartStyle::Braille => {
+            let width = if daily {
+                Some(data.len() * CHART_DAY_WIDTH)
+            } else {
+                None
+            };
+            graph.braille(height, width)
+        }
+        ChartStyle::Blocks => graph.chart(height),
+    }
+}
+
+/// How to scale graphs of the field `name`, from the `scales` in the config. A shared scale
+/// becomes a fixed one covering every shared metric's values in the whole of `weather`, so they
+/// can be compared, and so that each graph of a metric has the same range.
+fn get_scale(config: &Config, name: &str, weather: &Forecast) -> Scale {
+    let data = [&weather.hourly.data[..], &weather.daily.data[..]];
+    let scale = |metric: &Metric| config.scales.get(metric).cloned().unwrap_or_default();
+    match name.parse::<Metric>().map(|m| scale(&m)) {
+        Ok(Scale::Shared) => config
+            .scales
+            .keys()
+            .filter(|m| scale(m) == Scale::Shared)
+            .flat_map(|m| data.iter().flat_map(move |d| m.values(d)))
+            .flatten()
+            .fold(None, |range, v| match range {
+                None => Some((v, v)),
+                Some((min, max)) => Some((v.min(min), v.max(max))),
+            })
+            .map_or(Scale::Auto, |(min, max)| Scale::Fixed(min, max)),
+        Ok(scale) => scale,
+        Err(_) => Scale::Auto,
+    }
+}
+
+fn find_closest_time_position<Tz: TimeZone>(
+    time: &DateTime<Tz>,
+    times: &[DateTime<Tz>],
+) -> Option<usize> {
     let current_time = time;
     times
         .iter()
@@ -296,6 +1055,17 @@ fn find_closest_time_position(time: &DateTime<Local>, times: &[DateTime<Local>])
         .position(|time| current_time.date() == time.date() && current_time.hour() == time.hour())
 }
 
+/// `time` with the location's UTC offset, or the computer's if it isn't known.
+fn local_time(time: i64, offset: Option<i32>) -> DateTime<FixedOffset> {
+    match offset {
+        Some(offset) => FixedOffset::east(offset).timestamp(time, 0),
+        None => {
+            let local = Local.timestamp(time, 0);
+            local.with_timezone(&local.offset().fix())
+        }
+    }
+}
+
 fn get_wind_bearing_icon<'a>(bearing: u32) -> &'a str {
     info!("current wind bearing: {}°", (bearing + 180) % 360);
 
@@ -343,6 +1113,387 @@ fn get_wind_bearing_icon<'a>(bearing: u32) -> &'a str {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::color::Color;
+    use crate::forecast::DataBlock;
+    use crate::test_server::darksky_forecast;
+
+    fn sites(args: &[&str]) -> Result<Vec<Site>> {
+        let config: Config = toml::from_str(
+            r#"
+            lat = 1.0
+            lon = 2.0
+            default_location = "home"
+
+            [locations.home]
+            lat = 49.9
+            lon = -97.14
+
+            [locations.office]
+            lat = 43.65
+            lon = -79.38
+            label = "Toronto office"
+            timezone = "America/Toronto"
+
+            [locations.nowhere]
+            label = "Nowhere"
+            "#,
+        )
+        .unwrap();
+        let matches = app::build_cli().get_matches_from(args);
+        get_sites(&config, &matches)
+    }
+
+    fn site(args: &[&str]) -> Result<Site> {
+        Ok(sites(args)?.remove(0))
+    }
+
+    #[test]
+    fn saved_locations() {
+        let home = site(&["weather"]).unwrap();
+        assert_eq!((49.9, -97.14), (home.lat, home.lon));
+        assert_eq!(Some("home".to_string()), home.name);
+
+        let office = site(&["weather", "--location", "office"]).unwrap();
+        assert_eq!((43.65, -79.38), (office.lat, office.lon));
+        assert_eq!(Some("Toronto office".to_string()), office.name);
+        assert_eq!(Some(chrono_tz::America::Toronto), office.timezone);
+
+        let coordinates = site(&["weather", "--location", "office", "10", "20"]).unwrap();
+        assert_eq!((10., 20.), (coordinates.lat, coordinates.lon));
+        assert_eq!(None, coordinates.name);
+
+        match site(&["weather", "--location", "cottage"]) {
+            Err(Error::UnknownLocation(ref name)) if name == "cottage" => {}
+            r => panic!("expected an unknown location error, got {:?}", r),
+        }
+        match site(&["weather", "--location", "nowhere"]) {
+            Err(Error::InvalidLocation(ref name)) if name == "nowhere" => {}
+            r => panic!("expected an invalid location error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn site_timezone() {
+        let config: Config = toml::from_str(r#"provider = "met-norway""#).unwrap();
+        let response: Value =
+            serde_json::from_str(include_str!("../tests/fixtures/met-norway.json")).unwrap();
+        let site = Site {
+            timezone: Some(chrono_tz::America::Toronto),
+            ..Site::default()
+        };
+
+        let mut forecasts = parse_responses(&config, vec![(site, Ok((response, None)))]);
+        let (weather, _) = forecasts.remove(0).1.unwrap();
+        assert_eq!(Some("America/Toronto".to_string()), weather.timezone);
+        assert_eq!(Some(-4 * 3600), weather.utc_offset);
+
+        // The provider's own offset wins.
+        let mut weather = darksky_forecast();
+        weather.default_timezone(chrono_tz::America::Toronto);
+        assert_eq!(Some(-6 * 3600), weather.utc_offset);
+    }
+
+    #[test]
+    fn several_locations() {
+        let names = |args| -> Vec<Option<String>> {
+            sites(args)
+                .unwrap()
+                .into_iter()
+                .map(|s| s.location)
+                .collect()
+        };
+        assert_eq!(
+            vec![Some("office".to_string()), Some("home".to_string())],
+            names(&["weather", "--location", "office", "--location", "home"])
+        );
+        assert_eq!(
+            vec![Some("home".to_string())],
+            names(&["weather", "--location", "home", "--location", "home"])
+        );
+        assert_eq!(
+            vec![None],
+            names(&[
+                "weather",
+                "--location",
+                "office",
+                "--location",
+                "home",
+                "10",
+                "20"
+            ])
+        );
+    }
+
+    #[test]
+    fn failed_site_shown_with_others() {
+        let site = |name: &str| Site {
+            location: Some(name.to_string()),
+            name: Some(name.to_string()),
+            ..Site::default()
+        };
+        let weather = Forecast {
+            place: Some("home".to_string()),
+            ..darksky_forecast()
+        };
+        let forecasts = vec![
+            (site("home"), Ok((weather, None))),
+            (site("office"), Err(Error::MissingToken("darksky"))),
+        ];
+
+        let output = format_sites(&Config::default(), Mode::Plain, false, forecasts).unwrap();
+        let lines: Vec<_> = output.lines().collect();
+        assert!(lines[0].starts_with("home: 18°"), "{}", output);
+        assert_eq!("office: the darksky provider needs a token", lines[1]);
+
+        let forecasts = vec![(site("office"), Err(Error::MissingToken("darksky")))];
+        assert!(format_sites(&Config::default(), Mode::Plain, false, forecasts).is_err());
+
+        let outputs = vec![
+            ("home".to_string(), Ok(json!(1))),
+            ("office".to_string(), Err(Error::MissingToken("darksky"))),
+        ];
+        assert_eq!(
+            json!({ "home": 1, "office": { "error": "the darksky provider needs a token" } }),
+            keyed(outputs).unwrap()
+        );
+    }
+
+    #[test]
+    fn waybar_output() {
+        let weather = Forecast {
+            place: Some("Winnipeg & area".to_string()),
+            ..darksky_forecast()
+        };
+        let time = Local.timestamp(weather.current.time, 0);
+        let output = format_weather(
+            &Config::default(),
+            weather,
+            Mode::Waybar,
+            false,
+            time,
+            Some(0),
+        )
+        .unwrap();
+        let output: Value = serde_json::from_str(&output).unwrap();
+
+        let text = output["text"].as_str().unwrap();
+        assert!(text.starts_with("<span font_desc='Weather Icons'>"));
+        assert!(text.ends_with("</span>"));
+        let tooltip = output["tooltip"].as_str().unwrap();
+        assert!(tooltip.starts_with("Winnipeg &amp; area\nhourly pressure forecast:\n"));
+        assert_eq!(json!(["partly-cloudy-day", "stale"]), output["class"]);
+        assert_eq!(json!(0), output["percentage"]);
+    }
+
+    #[test]
+    fn waybar_tooltip_without_colours() {
+        let config = Config {
+            highlight: Some(Highlight {
+                position: Some(0),
+                fg: Some(Color::from(0xff_00_00_00)),
+                bg: None,
+            }),
+            ..Config::default()
+        };
+        let mut weather = darksky_forecast();
+        weather.current.icon = None;
+        let time = Local.timestamp(weather.current.time, 0);
+        let output = format_weather(&config, weather, Mode::Waybar, false, time, None).unwrap();
+        let output: Value = serde_json::from_str(&output).unwrap();
+
+        // The highlight is drawn with terminal colours, which Waybar would show as-is.
+        assert!(!output["tooltip"].as_str().unwrap().contains('\u{1b}'));
+        assert_eq!(json!([]), output["class"]);
+    }
+
+    #[test]
+    fn missing_values() {
+        let mut weather = darksky_forecast();
+        weather.current = DataPoint {
+            time: weather.current.time,
+            ..DataPoint::default()
+        };
+        weather.daily.data.clear();
+        let time = Local.timestamp(weather.current.time, 0);
+
+        let plain = format_weather(
+            &Config::default(),
+            weather.clone(),
+            Mode::Plain,
+            false,
+            time,
+            None,
+        )
+        .unwrap();
+        assert_eq!("?° ?. (?°)", plain);
+
+        let i3blocks = format_weather(
+            &Config::default(),
+            weather,
+            Mode::I3blocks,
+            false,
+            time,
+            None,
+        )
+        .unwrap();
+        assert!(i3blocks.contains("?° ?. (?°)"), "{}", i3blocks);
+        assert!(i3blocks.contains("? km/h"));
+    }
+
+    #[test]
+    fn alerts() {
+        let mut weather = darksky_forecast();
+        let now = weather.current.time;
+        let alert = |title: &str, severity, expires| Alert {
+            title: title.to_string(),
+            description: format!("{} in effect.", title),
+            severity: Some(severity),
+            expires: Some(expires),
+            ..Alert::default()
+        };
+        weather.alerts = vec![
+            alert("Frost Advisory", Severity::Advisory, now + 3600),
+            alert("Tornado Warning", Severity::Warning, now - 3600),
+            alert("Tornado Watch", Severity::Watch, now + 3600),
+        ];
+        let time = Local.timestamp(now, 0);
+        let format = |mode, long| {
+            format_weather(&Config::default(), weather.clone(), mode, long, time, None).unwrap()
+        };
+
+        // The expired warning doesn't count.
+        let line = format(Mode::I3blocks, false);
+        assert!(line.ends_with(" <span color='#de935f'>\u{26a0} 2</span>"));
+        assert!(!format(Mode::Plain, false).contains('\u{26a0}'));
+
+        let long = format(Mode::Plain, true);
+        assert!(long.contains("\u{26a0} Frost Advisory (until "));
+        assert!(long.contains("\nTornado Watch in effect."));
+        assert!(!long.contains("Tornado Warning"));
+    }
+
+    #[test]
+    fn long_charts() {
+        let config = Config {
+            chart_height: Some(3),
+            ..Config::default()
+        };
+        let weather = darksky_forecast();
+        let time = Local.timestamp(weather.current.time, 0);
+        let long = format_weather(&config, weather, Mode::Plain, true, time, None).unwrap();
+        let lines: Vec<_> = long.lines().collect();
+
+        let start = lines
+            .iter()
+            .position(|l| *l == "hourly pressure forecast:")
+            .unwrap();
+        assert!(lines[start + 1].contains('\u{2524}'), "{}", long);
+        assert!(lines[start + 2].contains('\u{2502}'));
+        assert!(lines[start + 4].contains('\u{2514}'));
+
+        let daily = long
+            .split("daily high temperature forecast:\n")
+            .nth(1)
+            .unwrap();
+        let weather = darksky_forecast();
+        assert!(daily.lines().nth(4).unwrap().trim_start().starts_with(
+            &local_time(weather.daily.data[0].time, weather.utc_offset)
+                .format("%a")
+                .to_string()
+        ));
+    }
+
+    #[test]
+    fn shared_scales() {
+        let config: Config = toml::from_str(
+            r#"
+            [scales]
+            temperature = "shared"
+            apparent_temperature = "shared"
+            pressure = [980, 1040]
+            "#,
+        )
+        .unwrap();
+        let weather = Forecast {
+            hourly: DataBlock {
+                summary: None,
+                data: vec![DataPoint {
+                    temperature: Some(-5.),
+                    apparent_temperature: Some(-12.),
+                    ..DataPoint::default()
+                }],
+            },
+            daily: DataBlock {
+                summary: None,
+                data: vec![DataPoint {
+                    temperature: Some(3.),
+                    apparent_temperature: Some(1.),
+                    ..DataPoint::default()
+                }],
+            },
+            ..Forecast::default()
+        };
+
+        let shared = get_scale(&config, "temperature", &weather);
+        assert_eq!(Scale::Fixed(-12., 3.), shared);
+        assert_eq!(shared, get_scale(&config, "apparent_temperature", &weather));
+        assert_eq!(
+            Scale::Fixed(980., 1040.),
+            get_scale(&config, "pressure", &weather)
+        );
+        assert_eq!(Scale::Auto, get_scale(&config, "humidity", &weather));
+        assert_eq!(Scale::Auto, get_scale(&config, "summary", &weather));
+
+        let error = toml::from_str::<Config>("[scales]\ntemp = \"zero\"").unwrap_err();
+        assert!(error.to_string().contains("no metric called \"temp\""));
+    }
+
+    #[test]
+    fn templates() {
+        let config: Config = toml::from_str(
+            r#"
+            format = "{temp:.0}{deg} {summary}"
+            stale_format = "old"
+            [formats]
+            tmux = "[{stale}] {place}"
+            "#,
+        )
+        .unwrap();
+        let weather = Forecast {
+            place: Some("Home #1".to_string()),
+            ..darksky_forecast()
+        };
+        let time = Local.timestamp(weather.current.time, 0);
+        let format =
+            |mode| format_weather(&config, weather.clone(), mode, false, time, Some(0)).unwrap();
+
+        assert_eq!(
+            format!(
+                "{:.0}° {} old",
+                weather.current.temperature.unwrap(),
+                weather.current.summary.clone().unwrap()
+            ),
+            format(Mode::Plain)
+        );
+        assert_eq!("[#[dim]old#[nodim]] Home ##1", format(Mode::Tmux));
+    }
+
+    #[test]
+    fn symbol_codes() {
+        match get_symbol_code_condition("lightrainshowers_night") {
+            Some((Condition::Rain, Some(Time::Night))) => {}
+            _ => panic!("expected rain at night"),
+        }
+        match get_symbol_code_condition("heavysnowshowersandthunder_polartwilight") {
+            Some((Condition::Thunderstorm, Some(Time::Day))) => {}
+            _ => panic!("expected a thunderstorm during the day"),
+        }
+        match get_symbol_code_condition("lightsleet") {
+            Some((Condition::Sleet, None)) => {}
+            _ => panic!("expected sleet at no particular time"),
+        }
+        assert!(get_symbol_code_condition("unknown_day").is_none());
+    }
 
     #[test]
     fn wind_bearing() {
@@ -3,8 +3,9 @@ extern crate clap;
 #[macro_use]
 extern crate log;
 
-use clap::Shell;
+use clap::{ArgMatches, Shell};
 use std::path::PathBuf;
+use weather::forecast::Metric;
 use weather::{app, Config};
 
 fn main() {
@@ -25,30 +26,60 @@ fn main() {
 
         app::build_cli().gen_completions_to(crate_name!(), shell, &mut std::io::stdout());
     } else {
-        let settings_path = matches.value_of("config").map_or(
-            dirs::home_dir()
-                .expect("couldn't determine home directory")
-                .join(".config")
-                .join(env!("CARGO_PKG_NAME"))
-                .join("config.toml"),
-            PathBuf::from,
-        );
-
-        let config = Config::from_path_buf(&settings_path).unwrap_or_else(|e| {
-            //TODO: handle as a proper error
-            eprintln!(
-                "Error parsing config file \"{}\": {}",
-                settings_path.display(),
-                e
-            );
-            std::process::exit(1);
-        });
+        let config = read_config(&matches);
 
-        debug!("config.toml:\n{:#?}", config);
+        let result = match matches.subcommand() {
+            ("alerts", Some(_)) => weather::alerts::run(&config, &matches),
+            ("bar", Some(bar_matches)) => weather::bar::run(&config, &matches, bar_matches),
+            ("hourly", Some(hourly_matches)) => {
+                weather::table::hourly(&config, &matches, hourly_matches)
+            }
+            ("daily", Some(daily_matches)) => {
+                weather::table::daily(&config, &matches, daily_matches)
+            }
+            ("chart", Some(chart_matches)) => weather::svg::run(&config, &matches, chart_matches),
+            ("notify", Some(notify_matches)) => {
+                weather::notify::run(&config, &matches, notify_matches)
+            }
+            _ => weather::run(&config, &matches),
+        };
 
-        if let Err(e) = weather::run(&config, &matches) {
+        if let Err(e) = result {
             eprintln!("{}", e);
             std::process::exit(1);
         };
     }
 }
+
+fn read_config(matches: &ArgMatches<'_>) -> Config {
+    let settings_path = matches.value_of("config").map_or(
+        dirs::home_dir()
+            .expect("couldn't determine home directory")
+            .join(".config")
+            .join(env!("CARGO_PKG_NAME"))
+            .join("config.toml"),
+        PathBuf::from,
+    );
+
+    let mut config = Config::from_path_buf(&settings_path).unwrap_or_else(|e| {
+        //TODO: handle as a proper error
+        eprintln!(
+            "Error parsing config file \"{}\": {}",
+            settings_path.display(),
+            e
+        );
+        std::process::exit(1);
+    });
+
+    // Graphs given on the command line take precedence.
+    if matches.is_present("graph") {
+        config.graphs = Some(values_t_or_exit!(matches, "graph", Metric));
+    }
+    if matches.is_present("daily_graph") {
+        config.daily_graphs = Some(values_t_or_exit!(matches, "daily_graph", Metric));
+    }
+
+    debug!("config.toml:\n{:#?}", config);
+
+    config
+}
@@ -0,0 +1,441 @@
+//! Desktop notifications for new alerts, precipitation starting soon, and the temperature
+//! crossing a threshold, for the `notify` subcommand.
+//!
+//! This is meant to be run every so often, e.g. from a systemd timer or cron. What was seen
+//! last time is kept for each location in a state file in the cache directory, so that each
+//! alert or change is only notified about once.
+
+use clap::ArgMatches;
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::process::Command;
+
+use crate::cache;
+use crate::config::Notify;
+use crate::forecast::{Alert, Forecast, Severity};
+use crate::{get_forecasts, local_time, Config, Error, Result};
+
+/// Notify about precipitation this many minutes away when `precip_within` isn't set.
+const DEFAULT_PRECIP_WITHIN: u64 = 60;
+/// Precipitation at least this likely is expected.
+const PRECIP_PROBABILITY: f64 = 0.5;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Urgency {
+    Low,
+    Normal,
+    Critical,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Notification {
+    pub summary: String,
+    pub body: String,
+    pub urgency: Urgency,
+}
+
+/// Somewhere to send notifications.
+pub trait Notifier {
+    fn notify(&self, notification: &Notification) -> Result<()>;
+}
+
+/// Sends notifications to the desktop's notification server, through the
+/// `org.freedesktop.Notifications` D-Bus interface.
+///
+/// This goes through `gdbus`, which comes with GLib, rather than linking to libdbus.
+pub struct DBus;
+
+impl Notifier for DBus {
+    fn notify(&self, notification: &Notification) -> Result<()> {
+        let urgency = match notification.urgency {
+            Urgency::Low => 0,
+            Urgency::Normal => 1,
+            Urgency::Critical => 2,
+        };
+
+        let output = Command::new("gdbus")
+            .args(vec![
+                "call",
+                "--session",
+                "--dest",
+                "org.freedesktop.Notifications",
+                "--object-path",
+                "/org/freedesktop/Notifications",
+                "--method",
+                "org.freedesktop.Notifications.Notify",
+            ])
+            // app_name, replaces_id, app_icon, summary, body, actions, hints, expire_timeout
+            .arg(gvariant_string(env!("CARGO_PKG_NAME")))
+            .arg("0")
+            .arg(gvariant_string(""))
+            .arg(gvariant_string(&notification.summary))
+            .arg(gvariant_string(&notification.body))
+            .arg("@as []")
+            .arg(format!("{{'urgency': <byte {}>}}", urgency))
+            .arg("-1")
+            .output()?;
+
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(Error::Notify(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ))
+        }
+    }
+}
+
+/// Prints notifications instead of sending them, for `--print`.
+pub struct Print;
+
+impl Notifier for Print {
+    fn notify(&self, notification: &Notification) -> Result<()> {
+        println!("{}\n{}\n", notification.summary, notification.body);
+        Ok(())
+    }
+}
+
+/// What was seen the last time, for one location.
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+struct State {
+    /// The alerts that have been notified about.
+    #[serde(default)]
+    seen_alerts: BTreeSet<String>,
+    temperature: Option<f64>,
+    /// When precipitation was expected to start, if it's been notified about.
+    precipitation: Option<i64>,
+}
+
+pub fn run(
+    config: &Config,
+    matches: &ArgMatches<'_>,
+    notify_matches: &ArgMatches<'_>,
+) -> Result<()> {
+    let notifier: Box<dyn Notifier> = if notify_matches.is_present("print") {
+        Box::new(Print)
+    } else {
+        Box::new(DBus)
+    };
+
+    let dir = cache::dir();
+    if dir.is_none() {
+        warn!("there's no cache directory to keep state in, so notifications may repeat");
+    }
+
+    for (_, weather, stale_since) in get_forecasts(config, matches)? {
+        // An old forecast has nothing new to say.
+        if stale_since.is_some() {
+            continue;
+        }
+        send(&*notifier, &config.notify, &weather, dir.as_deref())?;
+    }
+
+    Ok(())
+}
+
+/// Send the notifications for `weather`, keeping what's been seen in `dir`.
+fn send(
+    notifier: &dyn Notifier,
+    settings: &Notify,
+    weather: &Forecast,
+    dir: Option<&Path>,
+) -> Result<()> {
+    let name = format!(
+        "notify-{:.4},{:.4}.json",
+        weather.latitude, weather.longitude
+    );
+    let mut state = dir.and_then(|d| cache::read(d, &name)).unwrap_or_default();
+
+    for notification in check(settings, weather, &mut state) {
+        notifier.notify(&notification)?;
+    }
+
+    // Only once everything's been sent, so that anything that failed is tried again.
+    if let Some(dir) = dir {
+        cache::write(dir, &name, &state)?;
+    }
+    Ok(())
+}
+
+/// Work out what's new since `state`, and update it.
+fn check(settings: &Notify, weather: &Forecast, state: &mut State) -> Vec<Notification> {
+    let mut notifications = Vec::new();
+    let place = weather
+        .place
+        .as_ref()
+        .map(|p| format!("{}: ", p))
+        .unwrap_or_default();
+
+    let alerts = weather.active_alerts();
+    for alert in &alerts {
+        if !state.seen_alerts.contains(&alert_id(alert)) {
+            notifications.push(Notification {
+                summary: format!("{}\u{26a0} {}", place, alert.title),
+                body: alert.description.trim().to_string(),
+                urgency: match alert.severity {
+                    Some(Severity::Warning) => Urgency::Critical,
+                    _ => Urgency::Normal,
+                },
+            });
+        }
+    }
+    // Forget the ones that are gone, so the state doesn't keep growing.
+    state.seen_alerts = alerts.iter().map(|a| alert_id(a)).collect();
+
+    let now = weather.current.time;
+    let within = settings.precip_within.unwrap_or(DEFAULT_PRECIP_WITHIN) as i64 * 60;
+    let precipitating = weather.current.precip_intensity.unwrap_or_default() > 0.;
+    // Each hour's forecast covers the whole hour, so the one that's already started counts too.
+    let start = weather
+        .hourly
+        .data
+        .iter()
+        .filter(|d| d.time + 60 * 60 > now && d.time <= now + within)
+        .find(|d| d.precip_probability.unwrap_or_default() >= PRECIP_PROBABILITY);
+    match start {
+        Some(d) if !precipitating && state.precipitation != Some(d.time) => {
+            let kind = d.precip_type.map_or("precipitation", |p| p.name());
+            let mut body = format!(
+                "{:.0}% chance",
+                d.precip_probability.unwrap_or_default() * 100.
+            );
+            if let Some(intensity) = d.precip_intensity {
+                body.push_str(&format!(", {:.1} mm/h", intensity));
+            }
+            notifications.push(Notification {
+                summary: format!(
+                    "{}{} likely from {}",
+                    place,
+                    capitalize(kind),
+                    local_time(d.time, weather.utc_offset).format("%H:%M")
+                ),
+                body,
+                urgency: Urgency::Normal,
+            });
+            state.precipitation = Some(d.time);
+        }
+        Some(_) => {}
+        None if !precipitating => state.precipitation = None,
+        None => {}
+    }
+
+    if let (Some(previous), Some(current)) = (state.temperature, weather.current.temperature) {
+        for &threshold in &settings.temperatures {
+            let change = if previous < threshold && current >= threshold {
+                Some(("above", "up"))
+            } else if previous >= threshold && current < threshold {
+                Some(("below", "down"))
+            } else {
+                None
+            };
+            if let Some((side, direction)) = change {
+                notifications.push(Notification {
+                    summary: format!("{}Temperature {} {}°", place, side, threshold),
+                    body: format!(
+                        "It's {:.0}° now, {} from {:.0}°.",
+                        current, direction, previous
+                    ),
+                    urgency: Urgency::Low,
+                });
+            }
+        }
+    }
+    if weather.current.temperature.is_some() {
+        state.temperature = weather.current.temperature;
+    }
+
+    notifications
+}
+
+/// Tell alerts apart by their link if they have one, or else their title and when they were
+/// issued.
+fn alert_id(alert: &Alert) -> String {
+    match alert.uri {
+        Some(ref uri) => uri.clone(),
+        None => format!("{}@{}", alert.title, alert.time),
+    }
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Quote `text` as a GVariant string, which is how `gdbus` reads its arguments.
+fn gvariant_string(text: &str) -> String {
+    format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forecast::{DataBlock, DataPoint, PrecipType};
+    use std::cell::RefCell;
+
+    /// Keeps notifications instead of sending them.
+    #[derive(Default)]
+    struct Recorder(RefCell<Vec<Notification>>);
+
+    impl Notifier for Recorder {
+        fn notify(&self, notification: &Notification) -> Result<()> {
+            self.0.borrow_mut().push(Notification {
+                summary: notification.summary.clone(),
+                body: notification.body.clone(),
+                urgency: notification.urgency,
+            });
+            Ok(())
+        }
+    }
+
+    const NOW: i64 = 1_559_408_400;
+
+    fn weather(temperature: f64, alerts: Vec<Alert>) -> Forecast {
+        let hour = |hours: i64, probability: f64| DataPoint {
+            time: NOW + hours * 60 * 60,
+            precip_probability: Some(probability),
+            precip_intensity: Some(probability * 2.),
+            precip_type: Some(PrecipType::Rain),
+            ..DataPoint::default()
+        };
+        Forecast {
+            place: Some("Winnipeg".to_string()),
+            utc_offset: Some(-5 * 60 * 60),
+            current: DataPoint {
+                time: NOW,
+                temperature: Some(temperature),
+                precip_intensity: Some(0.),
+                ..DataPoint::default()
+            },
+            hourly: DataBlock {
+                summary: None,
+                data: vec![hour(0, 0.), hour(1, 0.7), hour(2, 0.9)],
+            },
+            alerts,
+            ..Forecast::default()
+        }
+    }
+
+    fn warning() -> Alert {
+        Alert {
+            title: "Severe Thunderstorm Warning".to_string(),
+            description: "Large hail.\n".to_string(),
+            time: NOW - 600,
+            expires: Some(NOW + 3600),
+            severity: Some(Severity::Warning),
+            ..Alert::default()
+        }
+    }
+
+    #[test]
+    fn new_alerts_once() {
+        let settings = Notify::default();
+        let mut state = State::default();
+
+        let first = check(&settings, &weather(20., vec![warning()]), &mut state);
+        assert!(first.contains(&Notification {
+            summary: "Winnipeg: \u{26a0} Severe Thunderstorm Warning".to_string(),
+            body: "Large hail.".to_string(),
+            urgency: Urgency::Critical,
+        }));
+
+        let again = check(&settings, &weather(20., vec![warning()]), &mut state);
+        assert!(again.is_empty(), "{:?}", again);
+
+        check(&settings, &weather(20., vec![]), &mut state);
+        assert!(state.seen_alerts.is_empty());
+    }
+
+    #[test]
+    fn precipitation_soon() {
+        let mut state = State::default();
+        let settings = Notify {
+            precip_within: Some(30),
+            temperatures: vec![],
+        };
+        assert!(check(&settings, &weather(20., vec![]), &mut state).is_empty());
+
+        let settings = Notify::default();
+        let notifications = check(&settings, &weather(20., vec![]), &mut state);
+        assert_eq!(1, notifications.len());
+        assert_eq!("Winnipeg: Rain likely from 13:00", notifications[0].summary);
+        assert_eq!("70% chance, 1.4 mm/h", notifications[0].body);
+        assert_eq!(Some(NOW + 60 * 60), state.precipitation);
+
+        assert!(check(&settings, &weather(20., vec![]), &mut state).is_empty());
+    }
+
+    #[test]
+    fn precipitation_this_hour() {
+        let mut state = State::default();
+        let settings = Notify {
+            precip_within: Some(30),
+            temperatures: vec![],
+        };
+        let mut weather = weather(20., vec![]);
+        weather.current.time = NOW + 20 * 60;
+        weather.hourly.data[0].precip_probability = Some(0.8);
+
+        assert_eq!(1, check(&settings, &weather, &mut state).len());
+        assert_eq!(Some(NOW), state.precipitation);
+    }
+
+    #[test]
+    fn temperature_thresholds() {
+        let settings = Notify {
+            precip_within: Some(0),
+            temperatures: vec![0., 30.],
+        };
+        let mut state = State::default();
+
+        // Nothing to compare with the first time.
+        assert!(check(&settings, &weather(28., vec![]), &mut state).is_empty());
+        assert!(check(&settings, &weather(29., vec![]), &mut state).is_empty());
+
+        let notifications = check(&settings, &weather(31., vec![]), &mut state);
+        assert_eq!(
+            vec![Notification {
+                summary: "Winnipeg: Temperature above 30°".to_string(),
+                body: "It's 31° now, up from 29°.".to_string(),
+                urgency: Urgency::Low,
+            }],
+            notifications
+        );
+
+        let notifications = check(&settings, &weather(-1., vec![]), &mut state);
+        assert_eq!(2, notifications.len());
+        assert_eq!("Winnipeg: Temperature below 0°", notifications[0].summary);
+    }
+
+    #[test]
+    fn state_kept_between_runs() {
+        let dir = cache::test_dir("notify-state");
+        let settings = Notify::default();
+        let recorder = Recorder::default();
+
+        send(
+            &recorder,
+            &settings,
+            &weather(20., vec![warning()]),
+            Some(&dir),
+        )
+        .unwrap();
+        assert_eq!(2, recorder.0.borrow().len());
+
+        send(
+            &recorder,
+            &settings,
+            &weather(20., vec![warning()]),
+            Some(&dir),
+        )
+        .unwrap();
+        assert_eq!(2, recorder.0.borrow().len());
+    }
+
+    #[test]
+    fn gvariant_quoting() {
+        assert_eq!(r"'it\'s a \\ test'", gvariant_string(r"it's a \ test"));
+    }
+}
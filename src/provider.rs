@@ -0,0 +1,146 @@
+mod darksky;
+mod met_norway;
+mod nws;
+mod open_meteo;
+
+use chrono::{DateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde_json::Value;
+
+pub use self::darksky::DarkSky;
+pub use self::met_norway::MetNorway;
+pub use self::nws::Nws;
+pub use self::open_meteo::OpenMeteo;
+use crate::forecast::Forecast;
+use crate::{cache, Config, Result};
+
+/// MET Norway, the NWS and Nominatim ask that the User-Agent identify the application and a way
+/// to contact its author.
+pub(crate) const DEFAULT_USER_AGENT: &str = concat!(
+    env!("CARGO_PKG_NAME"),
+    "/",
+    env!("CARGO_PKG_VERSION"),
+    " (https://github.com/mklein994/weather)"
+);
+
+/// The weather service to fetch forecasts from.
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProviderKind {
+    Darksky,
+    MetNorway,
+    Nws,
+    OpenMeteo,
+}
+
+impl Default for ProviderKind {
+    fn default() -> Self {
+        Self::Darksky
+    }
+}
+
+/// What to ask a provider for.
+#[derive(Clone, Debug, Default)]
+pub struct Request {
+    pub lat: f64,
+    pub lon: f64,
+    /// Ask for as many hours as the provider offers (168 for Dark Sky) instead of 48.
+    pub extend_hourly: bool,
+    /// Ask for the weather at this time (RFC 3339 or a UNIX timestamp) instead of now.
+    pub time: Option<String>,
+}
+
+/// Providers are `Send` so that several forecasts can be fetched at once.
+pub trait Provider: Send {
+    /// A short name for the provider, used in cache file names.
+    fn name(&self) -> &'static str;
+
+    /// The server it asks, so that responses from different servers are cached separately.
+    fn url(&self) -> &str;
+
+    /// Fetch the provider's response as-is.
+    fn fetch(&self, request: &Request) -> Result<Value>;
+
+    /// Convert a response returned from `fetch` (or saved to a file) into a `Forecast`.
+    fn parse(&self, raw: Value) -> Result<Forecast>;
+
+    /// Like `parse`, for a location in the given time zone, which is used when the response
+    /// doesn't say which one it's in.
+    fn parse_in(&self, raw: Value, timezone: Option<Tz>) -> Result<Forecast> {
+        let mut forecast = self.parse(raw)?;
+        if let Some(tz) = timezone {
+            forecast.default_timezone(tz);
+        }
+        Ok(forecast)
+    }
+}
+
+pub fn from_config(config: &Config) -> Box<dyn Provider> {
+    match config.provider {
+        ProviderKind::Darksky => {
+            let url = config
+                .api_url
+                .as_ref()
+                .map_or(darksky::API_URL, String::as_str);
+            let mut provider = DarkSky::new(config.token.clone(), url);
+            if let Some(ref path) = config.api_path {
+                provider = provider.path(path);
+            }
+            if let Some(ref time_machine_url) = config.time_machine_url {
+                provider = provider.time_machine_url(time_machine_url);
+            }
+            Box::new(provider)
+        }
+        ProviderKind::MetNorway => Box::new(MetNorway::new(
+            config
+                .api_url
+                .as_ref()
+                .map_or(met_norway::API_URL, String::as_str),
+            config
+                .user_agent
+                .as_ref()
+                .map_or(DEFAULT_USER_AGENT, String::as_str),
+            cache::dir(),
+        )),
+        ProviderKind::Nws => Box::new(Nws::new(
+            config.api_url.as_ref().map_or(nws::API_URL, String::as_str),
+            config
+                .user_agent
+                .as_ref()
+                .map_or(DEFAULT_USER_AGENT, String::as_str),
+            cache::dir(),
+        )),
+        ProviderKind::OpenMeteo => {
+            let url = config
+                .api_url
+                .as_ref()
+                .map_or(open_meteo::API_URL, String::as_str);
+            let mut provider = OpenMeteo::new(url);
+            if let Some(ref archive_url) = config.time_machine_url {
+                provider = provider.archive_url(archive_url);
+            }
+            Box::new(provider)
+        }
+    }
+}
+
+/// Parse the argument to `--historical`, which is either a UNIX timestamp or an RFC 3339 date.
+pub(crate) fn parse_time(time: &str) -> Result<DateTime<Utc>> {
+    match time.parse::<i64>() {
+        Ok(t) => Ok(Utc.timestamp(t, 0)),
+        Err(_) => Ok(DateTime::parse_from_rfc3339(time)?.with_timezone(&Utc)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_time_formats() {
+        let expected = Utc.ymd(2019, 6, 1).and_hms(17, 0, 0);
+        assert_eq!(expected, parse_time("1559408400").unwrap());
+        assert_eq!(expected, parse_time("2019-06-01T12:00:00-05:00").unwrap());
+        assert!(parse_time("yesterday").is_err());
+    }
+}
@@ -0,0 +1,295 @@
+use darksky::models::{
+    Alert as DarkskyAlert, Datablock, Datapoint, Forecast as DarkskyForecast, Icon as DarkskyIcon,
+    PrecipitationType, Severity as DarkskySeverity,
+};
+use reqwest::Client;
+use serde_json::Value;
+use std::convert::TryFrom;
+
+use super::{Provider, Request};
+use crate::forecast::{Alert, DataBlock, DataPoint, Forecast, Icon, PrecipType, Severity};
+use crate::{Error, Result};
+
+pub const API_URL: &str = "https://api.darksky.net";
+pub const API_PATH: &str = "forecast";
+
+/// A Dark Sky API client.
+///
+/// This also works with services that copied its API, such as Pirate Weather, by pointing it
+/// at their URL.
+pub struct DarkSky {
+    client: Client,
+    token: Option<String>,
+    url: String,
+    path: String,
+    time_machine_url: String,
+}
+
+impl DarkSky {
+    pub fn new(token: Option<String>, url: &str) -> Self {
+        let url = url.trim_end_matches('/').to_string();
+        Self {
+            client: Client::new(),
+            token,
+            time_machine_url: url.clone(),
+            url,
+            path: API_PATH.to_string(),
+        }
+    }
+
+    /// Use `path` instead of `forecast` as the first part of the request path.
+    pub fn path(mut self, path: &str) -> Self {
+        self.path = path.trim_matches('/').to_string();
+        self
+    }
+
+    /// Send Time Machine requests to a different host than forecast requests.
+    pub fn time_machine_url(mut self, url: &str) -> Self {
+        self.time_machine_url = url.trim_end_matches('/').to_string();
+        self
+    }
+}
+
+impl Provider for DarkSky {
+    fn name(&self) -> &'static str {
+        "darksky"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn fetch(&self, request: &Request) -> Result<Value> {
+        let token = self.token.as_ref().ok_or(Error::MissingToken("darksky"))?;
+
+        // Call the appropriate API if showing historical weather.
+        let url = if let Some(ref time) = request.time {
+            format!(
+                "{}/{}/{}/{},{},{}",
+                self.time_machine_url, self.path, token, request.lat, request.lon, time
+            )
+        } else {
+            format!(
+                "{}/{}/{}/{},{}",
+                self.url, self.path, token, request.lat, request.lon
+            )
+        };
+
+        let mut query = vec![("exclude", "minutely"), ("units", "ca"), ("lang", "en")];
+        if request.extend_hourly {
+            query.push(("extend", "hourly"));
+        }
+
+        let mut response = self
+            .client
+            .get(&url)
+            .query(&query)
+            .send()?
+            .error_for_status()?;
+
+        Ok(response.json()?)
+    }
+
+    fn parse(&self, raw: Value) -> Result<Forecast> {
+        let forecast: DarkskyForecast = serde_json::from_value(raw)?;
+        Forecast::try_from(forecast)
+    }
+}
+
+impl TryFrom<DarkskyForecast> for Forecast {
+    type Error = Error;
+
+    fn try_from(f: DarkskyForecast) -> Result<Self> {
+        Ok(Self {
+            place: None,
+            latitude: f.latitude,
+            longitude: f.longitude,
+            timezone: Some(f.timezone),
+            utc_offset: f.offset.map(|hours| (hours * 60. * 60.) as i32),
+            current: f
+                .currently
+                .ok_or(Error::Incomplete("the current weather"))?
+                .into(),
+            hourly: f
+                .hourly
+                .ok_or(Error::Incomplete("the hourly forecast"))?
+                .into(),
+            daily: f
+                .daily
+                .ok_or(Error::Incomplete("the daily forecast"))?
+                .into(),
+            alerts: f
+                .alerts
+                .unwrap_or_default()
+                .into_iter()
+                .map(Alert::from)
+                .collect(),
+        })
+    }
+}
+
+impl From<Datablock> for DataBlock {
+    fn from(b: Datablock) -> Self {
+        Self {
+            summary: b.summary,
+            data: b
+                .data
+                .unwrap_or_default()
+                .into_iter()
+                .map(DataPoint::from)
+                .collect(),
+        }
+    }
+}
+
+impl From<Datapoint> for DataPoint {
+    fn from(d: Datapoint) -> Self {
+        Self {
+            time: d.time as i64,
+            summary: d.summary,
+            icon: d.icon.map(Icon::from),
+            symbol_code: None,
+            temperature: d.temperature,
+            apparent_temperature: d.apparent_temperature,
+            temperature_high: d.temperature_high,
+            temperature_high_time: d.temperature_high_time.map(|t| t as i64),
+            temperature_low: d.temperature_low,
+            temperature_low_time: d.temperature_low_time.map(|t| t as i64),
+            dew_point: d.dew_point,
+            humidity: d.humidity,
+            pressure: d.pressure,
+            wind_speed: d.wind_speed,
+            wind_gust: d.wind_gust,
+            wind_bearing: d.wind_bearing,
+            cloud_cover: d.cloud_cover,
+            uv_index: d.uv_index.map(|u| u as f64),
+            visibility: d.visibility,
+            precip_intensity: d.precip_intensity,
+            precip_probability: d.precip_probability,
+            precip_accumulation: d.precip_accumulation,
+            precip_type: d.precip_type.map(|p| match p {
+                PrecipitationType::Rain => PrecipType::Rain,
+                PrecipitationType::Sleet => PrecipType::Sleet,
+                PrecipitationType::Snow => PrecipType::Snow,
+            }),
+            sunrise_time: d.sunrise_time.map(|t| t as i64),
+            sunset_time: d.sunset_time.map(|t| t as i64),
+            moon_phase: d.moon_phase,
+        }
+    }
+}
+
+impl From<DarkskyIcon> for Icon {
+    fn from(icon: DarkskyIcon) -> Self {
+        match icon {
+            DarkskyIcon::ClearDay => Icon::ClearDay,
+            DarkskyIcon::ClearNight => Icon::ClearNight,
+            DarkskyIcon::Cloudy => Icon::Cloudy,
+            DarkskyIcon::Fog => Icon::Fog,
+            DarkskyIcon::Hail => Icon::Hail,
+            DarkskyIcon::PartlyCloudyDay => Icon::PartlyCloudyDay,
+            DarkskyIcon::PartlyCloudyNight => Icon::PartlyCloudyNight,
+            DarkskyIcon::Rain => Icon::Rain,
+            DarkskyIcon::Sleet => Icon::Sleet,
+            DarkskyIcon::Snow => Icon::Snow,
+            DarkskyIcon::Thunderstorm => Icon::Thunderstorm,
+            DarkskyIcon::Tornado => Icon::Tornado,
+            DarkskyIcon::Wind => Icon::Wind,
+        }
+    }
+}
+
+impl From<DarkskyAlert> for Alert {
+    fn from(a: DarkskyAlert) -> Self {
+        Self {
+            title: a.title,
+            description: a.description,
+            time: a.time as i64,
+            expires: a.expires.map(|t| t as i64),
+            severity: Some(match a.severity {
+                DarkskySeverity::Advisory => Severity::Advisory,
+                DarkskySeverity::Watch => Severity::Watch,
+                DarkskySeverity::Warning => Severity::Warning,
+            }),
+            uri: Some(a.uri),
+            regions: a.regions,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{Route, TestServer};
+
+    const FIXTURE: &str = include_str!("../../tests/fixtures/darksky.json");
+
+    #[test]
+    fn fetch_from_custom_url() {
+        let server = TestServer::new(vec![Route::new("/v1/forecast/", FIXTURE)]);
+        let provider = DarkSky::new(Some("abc123".to_string()), &server.url).path("/v1/forecast/");
+
+        let raw = provider
+            .fetch(&Request {
+                lat: 49.9,
+                lon: -97.14,
+                extend_hourly: true,
+                ..Request::default()
+            })
+            .unwrap();
+
+        let requests = server.requests();
+        let request_line = requests[0].lines().next().unwrap();
+        assert!(request_line.starts_with("GET /v1/forecast/abc123/49.9,-97.14?"));
+        assert!(request_line.contains("units=ca"));
+        assert!(request_line.contains("extend=hourly"));
+
+        let forecast = provider.parse(raw).unwrap();
+        assert_eq!(Some(18.42), forecast.current.temperature);
+        assert_eq!(Some(Icon::PartlyCloudyDay), forecast.current.icon);
+        assert_eq!(3, forecast.hourly.data.len());
+        assert_eq!(Some(PrecipType::Rain), forecast.hourly.data[2].precip_type);
+        assert_eq!(Some(1_559_384_400), forecast.daily.data[0].sunrise_time);
+    }
+
+    #[test]
+    fn missing_block() {
+        let mut raw: Value = serde_json::from_str(FIXTURE).unwrap();
+        raw.as_object_mut().unwrap().remove("daily");
+
+        match DarkSky::new(None, "").parse(raw) {
+            Err(Error::Incomplete(part)) => assert_eq!("the daily forecast", part),
+            r => panic!("expected an incomplete forecast error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn fetch_time_machine() {
+        let forecast_server = TestServer::new(vec![]);
+        let time_machine_server = TestServer::new(vec![Route::new("/forecast/", FIXTURE)]);
+        let provider = DarkSky::new(Some("abc123".to_string()), &forecast_server.url)
+            .time_machine_url(&time_machine_server.url);
+
+        provider
+            .fetch(&Request {
+                lat: 49.9,
+                lon: -97.14,
+                time: Some("1559408400".to_string()),
+                ..Request::default()
+            })
+            .unwrap();
+
+        assert!(forecast_server.requests().is_empty());
+        assert!(time_machine_server.requests()[0]
+            .starts_with("GET /forecast/abc123/49.9,-97.14,1559408400?"));
+    }
+
+    #[test]
+    fn fetch_without_token() {
+        let provider = DarkSky::new(None, API_URL);
+        match provider.fetch(&Request::default()) {
+            Err(Error::MissingToken(_)) => {}
+            r => panic!("expected a missing token error, got {:?}", r),
+        }
+    }
+}
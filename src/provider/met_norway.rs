@@ -0,0 +1,648 @@
+use chrono::{DateTime, Local, TimeZone, Utc};
+use chrono_tz::Tz;
+use reqwest::header::{EXPIRES, IF_MODIFIED_SINCE, LAST_MODIFIED, USER_AGENT};
+use reqwest::{Client, StatusCode};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use super::{Provider, Request};
+use crate::cache;
+use crate::forecast::{
+    self, DataBlock, DataPoint, Daylight, Forecast, Icon, Intensity, PrecipType, SymbolCode,
+    SymbolKind,
+};
+use crate::{Error, Result};
+
+pub const API_URL: &str = "https://api.met.no";
+
+/// A client for MET Norway's Locationforecast API.
+///
+/// The API's terms ask clients to identify themselves, to not ask again before a response
+/// expires, and to make conditional requests after that. Responses are cached to do this.
+pub struct MetNorway {
+    client: Client,
+    url: String,
+    user_agent: String,
+    cache_dir: Option<PathBuf>,
+}
+
+/// A response saved along with the headers needed to decide when and how to ask again.
+#[derive(Debug, Deserialize, Serialize)]
+struct CachedResponse {
+    expires: Option<i64>,
+    last_modified: Option<String>,
+    body: Value,
+}
+
+impl MetNorway {
+    pub fn new(url: &str, user_agent: &str, cache_dir: Option<PathBuf>) -> Self {
+        Self {
+            client: Client::new(),
+            url: url.trim_end_matches('/').to_string(),
+            user_agent: user_agent.to_string(),
+            cache_dir,
+        }
+    }
+}
+
+impl Provider for MetNorway {
+    fn name(&self) -> &'static str {
+        "met-norway"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn fetch(&self, request: &Request) -> Result<Value> {
+        if request.time.is_some() {
+            return Err(Error::Unsupported("historical weather"));
+        }
+
+        // The API refuses coordinates with more than four decimal places.
+        let (lat, lon) = (format!("{:.4}", request.lat), format!("{:.4}", request.lon));
+        let cache_name = format!(
+            "met-norway-{}-{},{}.json",
+            cache::short_hash(&self.url),
+            lat,
+            lon
+        );
+
+        let cached: Option<CachedResponse> = self
+            .cache_dir
+            .as_ref()
+            .and_then(|dir| cache::read(dir, &cache_name));
+
+        if let Some(ref c) = cached {
+            if c.expires > Some(Utc::now().timestamp()) {
+                info!("cached response hasn't expired yet");
+                return Ok(c.body.clone());
+            }
+        }
+
+        let mut builder = self
+            .client
+            .get(&format!(
+                "{}/weatherapi/locationforecast/2.0/complete",
+                self.url
+            ))
+            .query(&[("lat", lat), ("lon", lon)])
+            .header(USER_AGENT, &self.user_agent[..]);
+
+        if let Some(last_modified) = cached.as_ref().and_then(|c| c.last_modified.as_ref()) {
+            builder = builder.header(IF_MODIFIED_SINCE, &last_modified[..]);
+        }
+
+        let mut response = builder.send()?;
+
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+        let expires = header(EXPIRES)
+            .and_then(|e| DateTime::parse_from_rfc2822(&e).ok())
+            .map(|e| e.timestamp());
+        let mut last_modified = header(LAST_MODIFIED);
+
+        let body: Value = match cached {
+            Some(c) if response.status() == StatusCode::NOT_MODIFIED => {
+                info!("forecast hasn't been modified since {:?}", c.last_modified);
+                last_modified = last_modified.or(c.last_modified);
+                c.body
+            }
+            _ => {
+                response.error_for_status_ref()?;
+                response.json()?
+            }
+        };
+
+        if let Some(ref dir) = self.cache_dir {
+            let entry = CachedResponse {
+                expires,
+                last_modified,
+                body,
+            };
+            if let Err(e) = cache::write(dir, &cache_name, &entry) {
+                warn!("couldn't cache response: {}", e);
+            }
+            return Ok(entry.body);
+        }
+
+        Ok(body)
+    }
+
+    fn parse(&self, raw: Value) -> Result<Forecast> {
+        self.parse_in(raw, None)
+    }
+
+    /// The response doesn't say which time zone the location is in, so days are split at
+    /// midnight in the one given, or the computer's own without one.
+    fn parse_in(&self, raw: Value, timezone: Option<Tz>) -> Result<Forecast> {
+        let response: Response = serde_json::from_value(raw)?;
+        Ok(match timezone {
+            Some(tz) => {
+                let mut forecast = response.into_forecast(&tz);
+                forecast.default_timezone(tz);
+                forecast
+            }
+            None => response.into_forecast(&Local),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Response {
+    geometry: Geometry,
+    properties: Properties,
+}
+
+#[derive(Debug, Deserialize)]
+struct Geometry {
+    /// Longitude, latitude and altitude.
+    coordinates: Vec<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Properties {
+    timeseries: Vec<Step>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Step {
+    time: String,
+    data: StepData,
+}
+
+#[derive(Debug, Deserialize)]
+struct StepData {
+    instant: Period,
+    next_1_hours: Option<Period>,
+    next_6_hours: Option<Period>,
+    next_12_hours: Option<Period>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Period {
+    summary: Option<Summary>,
+    #[serde(default)]
+    details: Details,
+}
+
+#[derive(Debug, Deserialize)]
+struct Summary {
+    symbol_code: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Details {
+    air_pressure_at_sea_level: Option<f64>,
+    air_temperature: Option<f64>,
+    air_temperature_max: Option<f64>,
+    air_temperature_min: Option<f64>,
+    cloud_area_fraction: Option<f64>,
+    dew_point_temperature: Option<f64>,
+    relative_humidity: Option<f64>,
+    ultraviolet_index_clear_sky: Option<f64>,
+    wind_from_direction: Option<f64>,
+    wind_speed: Option<f64>,
+    wind_speed_of_gust: Option<f64>,
+    precipitation_amount: Option<f64>,
+    probability_of_precipitation: Option<f64>,
+}
+
+impl Step {
+    fn timestamp(&self) -> i64 {
+        DateTime::parse_from_rfc3339(&self.time)
+            .map(|t| t.timestamp())
+            .unwrap_or_default()
+    }
+
+    /// The symbol code for the shortest period available, e.g. `partlycloudy_day`.
+    fn symbol_code(&self) -> Option<&str> {
+        [
+            &self.data.next_1_hours,
+            &self.data.next_6_hours,
+            &self.data.next_12_hours,
+        ]
+        .iter()
+        .filter_map(|p| p.as_ref().and_then(|p| p.summary.as_ref()))
+        .map(|s| &s.symbol_code[..])
+        .next()
+    }
+
+    fn data_point(&self) -> DataPoint {
+        let i = &self.data.instant.details;
+        let next = self
+            .data
+            .next_1_hours
+            .as_ref()
+            .or(self.data.next_6_hours.as_ref());
+        let (icon, summary, precip_type) = self.symbol_code().map_or((None, None, None), |s| {
+            let (icon, summary, precip_type) = describe(s);
+            (Some(icon), Some(summary), precip_type)
+        });
+
+        // m/s to km/h
+        let wind_speed = i.wind_speed.map(|s| s * 3.6);
+
+        DataPoint {
+            time: self.timestamp(),
+            summary,
+            icon,
+            symbol_code: self.symbol_code().map(str::to_string),
+            temperature: i.air_temperature,
+            apparent_temperature: i
+                .air_temperature
+                .map(|t| apparent_temperature(t, wind_speed.unwrap_or(0.))),
+            dew_point: i.dew_point_temperature,
+            humidity: i.relative_humidity.map(|h| h / 100.),
+            pressure: i.air_pressure_at_sea_level,
+            wind_speed,
+            wind_gust: i.wind_speed_of_gust.map(|s| s * 3.6),
+            wind_bearing: i.wind_from_direction,
+            cloud_cover: i.cloud_area_fraction.map(|c| c / 100.),
+            uv_index: i.ultraviolet_index_clear_sky,
+            precip_intensity: self
+                .data
+                .next_1_hours
+                .as_ref()
+                .and_then(|p| p.details.precipitation_amount),
+            precip_probability: next
+                .and_then(|p| p.details.probability_of_precipitation)
+                .map(|p| p / 100.),
+            precip_type,
+            ..DataPoint::default()
+        }
+    }
+}
+
+/// MET Norway doesn't give a "feels like" temperature, so use the wind chill when it applies.
+///
+/// This is the formula used by Environment Canada, for temperatures in °C and speeds in km/h.
+fn apparent_temperature(temperature: f64, wind_speed: f64) -> f64 {
+    if temperature > 10. || wind_speed <= 4.8 {
+        temperature
+    } else {
+        let v = wind_speed.powf(0.16);
+        13.12 + 0.6215 * temperature - 11.37 * v + 0.3965 * temperature * v
+    }
+}
+
+/// Map a MET Norway symbol code, e.g. `lightrainshowersandthunder_day`, to an icon, a summary and
+/// the type of precipitation.
+///
+/// The `Icon` is for templates and anything else that only knows Dark Sky's icons. The code
+/// itself is kept as well, and drawn by `get_symbol_code_condition`.
+fn describe(symbol_code: &str) -> (Icon, String, Option<PrecipType>) {
+    let symbol = match SymbolCode::parse(symbol_code) {
+        Some(s) => s,
+        None => {
+            warn!("unknown symbol code: {}", symbol_code);
+            return (Icon::Cloudy, symbol_code.to_string(), None);
+        }
+    };
+
+    let (clear, partly_cloudy) = if symbol.daylight == Some(Daylight::Night) {
+        (Icon::ClearNight, Icon::PartlyCloudyNight)
+    } else {
+        (Icon::ClearDay, Icon::PartlyCloudyDay)
+    };
+
+    let precip_type = match symbol.kind {
+        SymbolKind::Clear => return (clear, "Clear".to_string(), None),
+        SymbolKind::Fair => return (partly_cloudy, "Fair".to_string(), None),
+        SymbolKind::PartlyCloudy => return (partly_cloudy, "Partly Cloudy".to_string(), None),
+        SymbolKind::Cloudy => return (Icon::Cloudy, "Cloudy".to_string(), None),
+        SymbolKind::Fog => return (Icon::Fog, "Fog".to_string(), None),
+        SymbolKind::Precipitation(p) => p,
+    };
+
+    let mut summary = Vec::new();
+    match symbol.intensity {
+        Some(Intensity::Light) => summary.push("Light"),
+        Some(Intensity::Heavy) => summary.push("Heavy"),
+        None => {}
+    }
+
+    let icon = match precip_type {
+        PrecipType::Rain => {
+            summary.push("Rain");
+            Icon::Rain
+        }
+        PrecipType::Sleet => {
+            summary.push("Sleet");
+            Icon::Sleet
+        }
+        PrecipType::Snow => {
+            summary.push("Snow");
+            Icon::Snow
+        }
+    };
+
+    if symbol.showers {
+        summary.push("Showers");
+    }
+
+    let icon = if symbol.thunder {
+        summary.push("and Thunder");
+        Icon::Thunderstorm
+    } else {
+        icon
+    };
+
+    (icon, summary.join(" "), Some(precip_type))
+}
+
+impl Response {
+    /// The forecast, with its days split at midnight in the given time zone.
+    fn into_forecast<T: TimeZone>(self, tz: &T) -> Forecast {
+        let coordinates = &self.geometry.coordinates;
+        let lon = coordinates.first().cloned().unwrap_or_default();
+        let lat = coordinates.get(1).cloned().unwrap_or_default();
+        let steps = self.properties.timeseries;
+
+        let current = steps.first().map(Step::data_point).unwrap_or_default();
+
+        // The first couple of days are forecast hourly, and the rest every six hours.
+        let hourly: Vec<DataPoint> = steps
+            .iter()
+            .take_while(|s| s.data.next_1_hours.is_some())
+            .map(Step::data_point)
+            .collect();
+
+        let mut days: BTreeMap<_, Vec<&Step>> = BTreeMap::new();
+        for step in &steps {
+            days.entry(tz.timestamp(step.timestamp(), 0).date())
+                .or_default()
+                .push(step);
+        }
+
+        let daily = days
+            .into_iter()
+            .map(|(date, steps)| {
+                let time = date.and_hms(0, 0, 0).timestamp();
+                let temperatures = steps.iter().flat_map(|s| {
+                    let six_hours = s.data.next_6_hours.as_ref().map(|p| &p.details);
+                    vec![
+                        s.data.instant.details.air_temperature,
+                        six_hours.and_then(|d| d.air_temperature_max),
+                        six_hours.and_then(|d| d.air_temperature_min),
+                    ]
+                    .into_iter()
+                    .flatten()
+                });
+                let high = temperatures
+                    .clone()
+                    .fold(None, |m: Option<f64>, t| Some(m.map_or(t, |m| m.max(t))));
+                let low =
+                    temperatures.fold(None, |m: Option<f64>, t| Some(m.map_or(t, |m| m.min(t))));
+
+                // Describe the day by its midday step, or the closest one to it.
+                let midday = tz.timestamp(time, 0) + chrono::Duration::hours(12);
+                let representative = steps
+                    .iter()
+                    .min_by_key(|s| (s.timestamp() - midday.timestamp()).abs())
+                    .expect("a day always has at least one step");
+                let point = representative.data_point();
+                let sun = forecast::sun_times(time, lat, lon);
+
+                DataPoint {
+                    time,
+                    summary: point.summary,
+                    icon: point.icon.map(|i| match i {
+                        Icon::ClearNight => Icon::ClearDay,
+                        Icon::PartlyCloudyNight => Icon::PartlyCloudyDay,
+                        i => i,
+                    }),
+                    symbol_code: point.symbol_code.map(|s| s.replace("_night", "_day")),
+                    temperature_high: high,
+                    temperature_low: low,
+                    pressure: point.pressure,
+                    wind_speed: point.wind_speed,
+                    wind_bearing: point.wind_bearing,
+                    precip_probability: point.precip_probability,
+                    precip_type: point.precip_type,
+                    sunrise_time: sun.map(|(rise, _)| rise),
+                    sunset_time: sun.map(|(_, set)| set),
+                    moon_phase: Some(forecast::moon_phase(time)),
+                    ..DataPoint::default()
+                }
+            })
+            .collect();
+
+        Forecast {
+            place: None,
+            latitude: lat,
+            longitude: lon,
+            timezone: None,
+            utc_offset: None,
+            current,
+            hourly: DataBlock {
+                summary: None,
+                data: hourly,
+            },
+            daily: DataBlock {
+                summary: None,
+                data: daily,
+            },
+            alerts: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{Route, TestServer};
+    use chrono::Timelike;
+
+    const FIXTURE: &str = include_str!("../../tests/fixtures/met-norway.json");
+    const PATH: &str = "/weatherapi/locationforecast/2.0/complete";
+
+    fn request() -> Request {
+        Request {
+            lat: 59.9333,
+            lon: 10.7166,
+            ..Request::default()
+        }
+    }
+
+    fn route(expires: &str) -> Route {
+        let mut route = Route::new(PATH, FIXTURE);
+        route.headers.push(("Expires", expires.to_string()));
+        route
+            .headers
+            .push(("Last-Modified", "Sat, 01 Jun 2019 16:40:00 GMT".to_string()));
+        route
+    }
+
+    #[test]
+    fn fetch_and_parse() {
+        let server = TestServer::new(vec![route("Sat, 01 Jun 2019 17:10:00 GMT")]);
+        let provider = MetNorway::new(&server.url, "weather-test", None);
+
+        let forecast = provider.parse(provider.fetch(&request()).unwrap()).unwrap();
+
+        let requests = server.requests();
+        assert!(requests[0].contains("lat=59.9333&lon=10.7166"));
+        assert!(requests[0]
+            .to_lowercase()
+            .contains("user-agent: weather-test"));
+
+        let c = &forecast.current;
+        assert_eq!(Some(14.6), c.temperature);
+        assert_eq!(Some(1012.3), c.pressure);
+        assert_eq!(Some(18.), c.wind_speed.map(|s| s.round()));
+        assert_eq!(Some(Icon::PartlyCloudyDay), c.icon);
+        assert_eq!(Some("partlycloudy_day"), c.symbol_code.as_deref());
+        assert_eq!(Some("Partly Cloudy".to_string()), c.summary);
+
+        assert_eq!(
+            vec![Some(1012.3), Some(1012.0), Some(1011.4)],
+            forecast
+                .hourly
+                .data
+                .iter()
+                .map(|d| d.pressure)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(Some(Icon::Thunderstorm), forecast.hourly.data[2].icon);
+
+        let d = &forecast.daily.data;
+        assert!(!d.is_empty());
+        assert!(d.iter().all(|d| d.temperature_high >= d.temperature_low));
+        assert_eq!(
+            Some(16.9),
+            d.iter()
+                .filter_map(|d| d.temperature_high)
+                .fold(None, |m: Option<f64>, t| {
+                    Some(m.map_or(t, |m| m.max(t)))
+                })
+        );
+    }
+
+    #[test]
+    fn days_in_location_timezone() {
+        let provider = MetNorway::new("", "weather-test", None);
+        let raw: Value = serde_json::from_str(FIXTURE).unwrap();
+
+        let forecast = provider
+            .parse_in(raw, Some(chrono_tz::Asia::Tokyo))
+            .unwrap();
+
+        assert_eq!(Some(9 * 3600), forecast.utc_offset);
+        assert!(!forecast.daily.data.is_empty());
+        for d in &forecast.daily.data {
+            let midnight = chrono_tz::Asia::Tokyo.timestamp(d.time, 0);
+            assert_eq!((0, 0), (midnight.hour(), midnight.minute()));
+        }
+    }
+
+    #[test]
+    fn conditional_request_after_expiry() {
+        let dir = cache::test_dir("met-norway-conditional");
+
+        let mut not_modified = Route::new(PATH, "");
+        not_modified.status = 304;
+        let server = TestServer::new(vec![not_modified]);
+        let entry = CachedResponse {
+            expires: Some(0),
+            last_modified: Some("Sat, 01 Jun 2019 16:40:00 GMT".to_string()),
+            body: serde_json::from_str(FIXTURE).unwrap(),
+        };
+        let name = format!(
+            "met-norway-{}-59.9333,10.7166.json",
+            cache::short_hash(&server.url)
+        );
+        cache::write(&dir, &name, &entry).unwrap();
+
+        let provider = MetNorway::new(&server.url, "weather-test", Some(dir));
+        let raw = provider.fetch(&request()).unwrap();
+
+        assert!(server.requests()[0]
+            .to_lowercase()
+            .contains("if-modified-since: sat, 01 jun 2019 16:40:00 gmt"));
+        assert_eq!(Some(14.6), provider.parse(raw).unwrap().current.temperature);
+    }
+
+    #[test]
+    fn no_request_before_expiry() {
+        let dir = cache::test_dir("met-norway-unexpired");
+        let server = TestServer::new(vec![route("Fri, 31 Dec 9999 23:59:59 GMT")]);
+        let provider = MetNorway::new(&server.url, "weather-test", Some(dir));
+
+        provider.fetch(&request()).unwrap();
+        provider.fetch(&request()).unwrap();
+
+        assert_eq!(1, server.requests().len());
+    }
+
+    #[test]
+    fn cached_per_server() {
+        let dir = cache::test_dir("met-norway-per-server");
+
+        let first = TestServer::new(vec![route("Fri, 31 Dec 9999 23:59:59 GMT")]);
+        MetNorway::new(&first.url, "weather-test", Some(dir.clone()))
+            .fetch(&request())
+            .unwrap();
+
+        let second = TestServer::new(vec![route("Fri, 31 Dec 9999 23:59:59 GMT")]);
+        MetNorway::new(&second.url, "weather-test", Some(dir))
+            .fetch(&request())
+            .unwrap();
+
+        assert_eq!(1, second.requests().len());
+    }
+
+    #[test]
+    fn symbol_codes() {
+        assert_eq!(
+            (Icon::ClearNight, "Clear".to_string(), None),
+            describe("clearsky_night")
+        );
+        assert_eq!(
+            (
+                Icon::Thunderstorm,
+                "Light Rain Showers and Thunder".to_string(),
+                Some(PrecipType::Rain)
+            ),
+            describe("lightrainshowersandthunder_day")
+        );
+        assert_eq!(
+            (
+                Icon::Thunderstorm,
+                "Light Snow Showers and Thunder".to_string(),
+                Some(PrecipType::Snow)
+            ),
+            describe("lightssnowshowersandthunder_polartwilight")
+        );
+        assert_eq!(
+            (
+                Icon::Sleet,
+                "Heavy Sleet".to_string(),
+                Some(PrecipType::Sleet)
+            ),
+            describe("heavysleet")
+        );
+        assert_eq!(
+            (
+                Icon::Sleet,
+                "Light Sleet".to_string(),
+                Some(PrecipType::Sleet)
+            ),
+            describe("lightsleet")
+        );
+    }
+
+    #[test]
+    fn wind_chill() {
+        assert_eq!(20., apparent_temperature(20., 30.));
+        assert_eq!(-5., apparent_temperature(-5., 2.));
+        assert_eq!(-26., apparent_temperature(-15., 30.).round());
+    }
+}
@@ -0,0 +1,929 @@
+use chrono::{DateTime, Utc};
+use reqwest::header::{ACCEPT, USER_AGENT};
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+
+use super::{Provider, Request};
+use crate::cache;
+use crate::forecast::{self, Alert, DataBlock, DataPoint, Forecast, Icon, PrecipType, Severity};
+use crate::{Error, Result};
+
+pub const API_URL: &str = "https://api.weather.gov";
+
+/// How long a cached grid lookup is used before looking it up again, in seconds. Offices and
+/// grids rarely change, but they do.
+const POINT_TTL: i64 = 7 * 24 * 60 * 60;
+
+/// A client for the US National Weather Service API at api.weather.gov.
+///
+/// Forecasts are looked up in two steps: the coordinates are resolved to a forecast office's
+/// grid, which then has its own forecast URLs. The first step rarely changes, so it's cached
+/// per coordinate pair for a week.
+pub struct Nws {
+    client: Client,
+    url: String,
+    user_agent: String,
+    cache_dir: Option<PathBuf>,
+}
+
+/// The parts of the `/points` lookup we need, as saved in the cache.
+#[derive(Debug, Deserialize, Serialize)]
+struct Point {
+    forecast: String,
+    forecast_hourly: String,
+    /// The raw gridpoint data, which has the only pressure forecast. Lookups cached before this
+    /// was saved don't have it.
+    #[serde(default)]
+    forecast_grid_data: Option<String>,
+    observation_stations: String,
+    time_zone: Option<String>,
+    /// The first observation station near the point, looked up from `observation_stations`.
+    station: Option<String>,
+    /// When it was looked up. Lookups cached before this was saved count as expired.
+    #[serde(default)]
+    fetched: i64,
+}
+
+impl Nws {
+    pub fn new(url: &str, user_agent: &str, cache_dir: Option<PathBuf>) -> Self {
+        Self {
+            client: Client::new(),
+            url: url.trim_end_matches('/').to_string(),
+            user_agent: user_agent.to_string(),
+            cache_dir,
+        }
+    }
+
+    fn get<T: DeserializeOwned>(&self, url: &str) -> Result<T> {
+        debug!("GET {}", url);
+        let mut response = self
+            .client
+            .get(url)
+            .header(USER_AGENT, &self.user_agent[..])
+            .header(ACCEPT, "application/geo+json")
+            .send()?
+            .error_for_status()?;
+
+        Ok(response.json()?)
+    }
+
+    /// Like `get`, for the parts of a forecast it can do without. If the request fails, it's
+    /// logged and treated as null.
+    fn get_optional(&self, url: &str, what: &str) -> Value {
+        self.get(url).unwrap_or_else(|e| {
+            warn!("couldn't get {}: {}", what, e);
+            Value::Null
+        })
+    }
+
+    fn point(&self, lat: f64, lon: f64) -> Result<Point> {
+        // The API redirects requests with more than four decimal places.
+        let coordinates = format!("{:.4},{:.4}", lat, lon);
+        // The lookup links to the server it came from, so each server has its own.
+        let cache_name = format!(
+            "nws-point-{}-{}.json",
+            cache::short_hash(&self.url),
+            coordinates
+        );
+        let now = Utc::now().timestamp();
+
+        if let Some(ref dir) = self.cache_dir {
+            if let Some(point) = cache::read::<Point>(dir, &cache_name) {
+                if now - point.fetched < POINT_TTL {
+                    info!("using cached grid lookup for {}", coordinates);
+                    return Ok(point);
+                }
+            }
+        }
+
+        let response: Value = self.get(&format!("{}/points/{}", self.url, coordinates))?;
+        let mut point: Point = serde_json::from_value(json!({
+            "forecast": response["properties"]["forecast"],
+            "forecast_hourly": response["properties"]["forecastHourly"],
+            "forecast_grid_data": response["properties"]["forecastGridData"],
+            "observation_stations": response["properties"]["observationStations"],
+            "time_zone": response["properties"]["timeZone"],
+            "fetched": now,
+        }))?;
+
+        let stations = self.get_optional(&point.observation_stations, "the nearby stations");
+        point.station = stations["observationStations"][0]
+            .as_str()
+            .map(str::to_string);
+
+        // If the stations couldn't be had, look them up again next time.
+        if stations.is_null() {
+            return Ok(point);
+        }
+
+        if let Some(ref dir) = self.cache_dir {
+            if let Err(e) = cache::write(dir, &cache_name, &point) {
+                warn!("couldn't cache grid lookup: {}", e);
+            }
+        }
+
+        Ok(point)
+    }
+}
+
+impl Provider for Nws {
+    fn name(&self) -> &'static str {
+        "nws"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn fetch(&self, request: &Request) -> Result<Value> {
+        if request.time.is_some() {
+            return Err(Error::Unsupported("historical weather"));
+        }
+
+        let point = self.point(request.lat, request.lon)?;
+
+        let mut hourly: Value = self.get(&format!("{}?units=si", point.forecast_hourly))?;
+        if !request.extend_hourly {
+            if let Some(periods) = hourly["properties"]["periods"].as_array_mut() {
+                periods.truncate(48);
+            }
+        }
+
+        let daily: Value = self.get(&format!("{}?units=si", point.forecast))?;
+
+        // Stations go offline, and the pressure and alerts are extra, so the forecast is shown
+        // without them.
+        let grid = match point.forecast_grid_data {
+            Some(ref url) => self.get_optional(url, "the gridpoint data"),
+            None => Value::Null,
+        };
+
+        let observation = match point.station {
+            Some(ref station) => self.get_optional(
+                &format!("{}/observations/latest", station),
+                "the latest observation",
+            ),
+            None => Value::Null,
+        };
+
+        let alerts = self.get_optional(
+            &format!(
+                "{}/alerts/active?point={:.4},{:.4}",
+                self.url, request.lat, request.lon
+            ),
+            "alerts",
+        );
+
+        Ok(json!({
+            "time": Utc::now().timestamp(),
+            "latitude": request.lat,
+            "longitude": request.lon,
+            "timeZone": point.time_zone,
+            "observation": observation,
+            "pressure": grid["properties"]["pressure"],
+            "hourly": hourly,
+            "daily": daily,
+            "alerts": alerts,
+        }))
+    }
+
+    fn parse(&self, raw: Value) -> Result<Forecast> {
+        let response: Response = serde_json::from_value(raw)?;
+        Ok(response.into())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Response {
+    /// When it was fetched. Responses saved before this was added don't have it.
+    time: Option<i64>,
+    latitude: f64,
+    longitude: f64,
+    time_zone: Option<String>,
+    observation: Option<Feature<Observation>>,
+    /// The hourly forecast has no pressure, so it's from the gridpoint data.
+    pressure: Option<Layer>,
+    hourly: Feature<Periods>,
+    daily: Feature<Periods>,
+    alerts: Option<Features<AlertProperties>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Feature<T> {
+    properties: T,
+}
+
+#[derive(Debug, Deserialize)]
+struct Features<T> {
+    features: Vec<Feature<T>>,
+}
+
+/// A value with a unit, e.g. `{ "unitCode": "wmoUnit:Pa", "value": 101320 }`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Quantity {
+    unit_code: String,
+    value: Option<f64>,
+}
+
+impl Quantity {
+    /// Convert to the units used by `Forecast`.
+    fn value(&self) -> Option<f64> {
+        let unit = self.unit_code.trim_start_matches("wmoUnit:");
+        self.value.map(|v| match unit {
+            "degF" => (v - 32.) * 5. / 9.,
+            "K" => v - 273.15,
+            "Pa" => v / 100.,
+            "m_s-1" => v * 3.6,
+            "m" => v / 1000.,
+            "percent" => v / 100.,
+            // degC, km_h-1, degree_(angle), hPa
+            _ => v,
+        })
+    }
+}
+
+/// A series from the gridpoint data, each value covering a span of time.
+#[derive(Debug, Deserialize)]
+struct Layer {
+    uom: String,
+    values: Vec<LayerValue>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LayerValue {
+    /// When the value starts, and for how long, e.g. "2019-06-01T17:00:00+00:00/PT2H".
+    valid_time: String,
+    value: Option<f64>,
+}
+
+impl Layer {
+    /// The value for `time`, in the units used by `Forecast`.
+    fn value_at(&self, time: i64) -> Option<f64> {
+        self.values
+            .iter()
+            .find(|v| match interval(&v.valid_time) {
+                Some((start, end)) => start <= time && time < end,
+                None => false,
+            })
+            .and_then(|v| {
+                Quantity {
+                    unit_code: self.uom.clone(),
+                    value: v.value,
+                }
+                .value()
+            })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Observation {
+    timestamp: String,
+    text_description: Option<String>,
+    icon: Option<String>,
+    #[serde(default)]
+    temperature: Quantity,
+    #[serde(default)]
+    dewpoint: Quantity,
+    #[serde(default)]
+    wind_direction: Quantity,
+    #[serde(default)]
+    wind_speed: Quantity,
+    #[serde(default)]
+    wind_gust: Quantity,
+    #[serde(default)]
+    barometric_pressure: Quantity,
+    #[serde(default)]
+    sea_level_pressure: Quantity,
+    #[serde(default)]
+    visibility: Quantity,
+    #[serde(default)]
+    relative_humidity: Quantity,
+    #[serde(default)]
+    wind_chill: Quantity,
+    #[serde(default)]
+    heat_index: Quantity,
+}
+
+#[derive(Debug, Deserialize)]
+struct Periods {
+    periods: Vec<Period>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Period {
+    start_time: String,
+    is_daytime: bool,
+    temperature: Option<f64>,
+    temperature_unit: Option<String>,
+    /// e.g. "15 km/h" or "10 to 20 mph"
+    wind_speed: Option<String>,
+    /// A compass point, e.g. "NW"
+    wind_direction: Option<String>,
+    icon: Option<String>,
+    short_forecast: Option<String>,
+    detailed_forecast: Option<String>,
+    #[serde(default)]
+    probability_of_precipitation: Quantity,
+    #[serde(default)]
+    dewpoint: Quantity,
+    #[serde(default)]
+    relative_humidity: Quantity,
+}
+
+impl Period {
+    fn time(&self) -> i64 {
+        timestamp(&self.start_time).unwrap_or_default()
+    }
+
+    fn temperature(&self) -> Option<f64> {
+        self.temperature
+            .map(|t| match self.temperature_unit.as_ref() {
+                Some(u) if u == "F" => (t - 32.) * 5. / 9.,
+                _ => t,
+            })
+    }
+
+    /// The highest speed mentioned, in km/h.
+    fn wind_speed(&self) -> Option<f64> {
+        let speed = self.wind_speed.as_ref()?;
+        let max = speed
+            .split_whitespace()
+            .filter_map(|w| w.parse::<f64>().ok())
+            .fold(None, |max: Option<f64>, s| {
+                Some(max.map_or(s, |m| m.max(s)))
+            })?;
+        Some(if speed.ends_with("mph") {
+            max * 1.609_344
+        } else {
+            max
+        })
+    }
+
+    fn wind_bearing(&self) -> Option<f64> {
+        const POINTS: [&str; 16] = [
+            "N", "NNE", "NE", "ENE", "E", "ESE", "SE", "SSE", "S", "SSW", "SW", "WSW", "W", "WNW",
+            "NW", "NNW",
+        ];
+        let direction = self.wind_direction.as_ref()?;
+        POINTS
+            .iter()
+            .position(|p| p == direction)
+            .map(|i| i as f64 * 22.5)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AlertProperties {
+    event: String,
+    headline: Option<String>,
+    description: Option<String>,
+    sent: String,
+    expires: Option<String>,
+    severity: Option<String>,
+    area_desc: Option<String>,
+    #[serde(rename = "@id")]
+    id: Option<String>,
+}
+
+fn timestamp(time: &str) -> Option<i64> {
+    DateTime::parse_from_rfc3339(time)
+        .map(|t| t.timestamp())
+        .ok()
+}
+
+/// The start and end of an ISO 8601 interval given as a start time and a duration in days,
+/// hours and minutes, e.g. "2019-06-01T17:00:00+00:00/P1DT6H".
+fn interval(interval: &str) -> Option<(i64, i64)> {
+    let mut parts = interval.splitn(2, '/');
+    let start = timestamp(parts.next()?)?;
+    let duration = parts.next()?;
+    if !duration.starts_with('P') {
+        return None;
+    }
+
+    let mut seconds = 0;
+    let mut number = String::new();
+    let mut in_time = false;
+    for c in duration[1..].chars() {
+        let unit = match c {
+            'T' => {
+                in_time = true;
+                continue;
+            }
+            '0'..='9' => {
+                number.push(c);
+                continue;
+            }
+            'D' if !in_time => 24 * 60 * 60,
+            'H' if in_time => 60 * 60,
+            'M' if in_time => 60,
+            _ => return None,
+        };
+        seconds += number.parse::<i64>().ok()? * unit;
+        number.clear();
+    }
+
+    Some((start, start + seconds))
+}
+
+/// Map an icon URL like `https://api.weather.gov/icons/land/night/tsra_sct,40/rain?size=small`
+/// to an icon and precipitation type, using the first condition in the URL.
+fn parse_icon(url: &str, is_daytime: bool) -> (Option<Icon>, Option<PrecipType>) {
+    let code = url
+        .split('?')
+        .next()
+        .and_then(|path| path.rsplit("/day/").next())
+        .and_then(|path| path.rsplit("/night/").next())
+        .and_then(|conditions| conditions.split('/').next())
+        .and_then(|condition| condition.split(',').next())
+        .unwrap_or_default();
+
+    let icon = match code {
+        "skc" | "few" | "hot" | "cold" => {
+            if is_daytime {
+                Icon::ClearDay
+            } else {
+                Icon::ClearNight
+            }
+        }
+        "sct" | "bkn" => {
+            if is_daytime {
+                Icon::PartlyCloudyDay
+            } else {
+                Icon::PartlyCloudyNight
+            }
+        }
+        "ovc" => Icon::Cloudy,
+        "fog" | "haze" | "smoke" | "dust" => Icon::Fog,
+        "rain" | "rain_showers" | "rain_showers_hi" => Icon::Rain,
+        "snow" | "blizzard" => Icon::Snow,
+        "rain_snow" | "rain_sleet" | "snow_sleet" | "sleet" | "fzra" | "rain_fzra"
+        | "snow_fzra" => Icon::Sleet,
+        "tsra" | "tsra_sct" | "tsra_hi" => Icon::Thunderstorm,
+        "tornado" => Icon::Tornado,
+        c if c.starts_with("wind_") || c == "hurricane" || c == "tropical_storm" => Icon::Wind,
+        _ => return (None, None),
+    };
+
+    let precip_type = match icon {
+        Icon::Rain | Icon::Thunderstorm => Some(PrecipType::Rain),
+        Icon::Sleet => Some(PrecipType::Sleet),
+        Icon::Snow => Some(PrecipType::Snow),
+        _ => None,
+    };
+
+    (Some(icon), precip_type)
+}
+
+impl From<Response> for Forecast {
+    fn from(r: Response) -> Self {
+        let (lat, lon) = (r.latitude, r.longitude);
+
+        let observed_pressure = r.observation.as_ref().and_then(|o| {
+            o.properties
+                .sea_level_pressure
+                .value()
+                .or_else(|| o.properties.barometric_pressure.value())
+        });
+        // Without a pressure forecast, the latest observation is the best guess.
+        let pressure = |time: i64| {
+            r.pressure
+                .as_ref()
+                .and_then(|p| p.value_at(time))
+                .or(observed_pressure)
+        };
+
+        let hourly: Vec<DataPoint> = r
+            .hourly
+            .properties
+            .periods
+            .iter()
+            .map(|p| {
+                let (icon, precip_type) = p
+                    .icon
+                    .as_ref()
+                    .map_or((None, None), |i| parse_icon(i, p.is_daytime));
+                DataPoint {
+                    time: p.time(),
+                    summary: p.short_forecast.clone(),
+                    icon,
+                    temperature: p.temperature(),
+                    dew_point: p.dewpoint.value(),
+                    humidity: p.relative_humidity.value(),
+                    pressure: pressure(p.time()),
+                    wind_speed: p.wind_speed(),
+                    wind_bearing: p.wind_bearing(),
+                    precip_probability: p.probability_of_precipitation.value(),
+                    precip_type,
+                    ..DataPoint::default()
+                }
+            })
+            .collect();
+
+        // The daily forecast alternates between day and night periods. Pair each day with the
+        // night after it for the high and low.
+        let periods = &r.daily.properties.periods;
+        let daily: Vec<DataPoint> = periods
+            .iter()
+            .enumerate()
+            .filter(|(i, p)| p.is_daytime || *i == 0)
+            .map(|(i, p)| {
+                let night = if p.is_daytime {
+                    periods.get(i + 1)
+                } else {
+                    Some(p)
+                };
+                let time = p.time();
+                let (icon, precip_type) = p
+                    .icon
+                    .as_ref()
+                    .map_or((None, None), |i| parse_icon(i, true));
+                let sun = forecast::sun_times(time, lat, lon);
+                DataPoint {
+                    time,
+                    summary: p.short_forecast.clone(),
+                    icon,
+                    temperature_high: if p.is_daytime { p.temperature() } else { None },
+                    temperature_low: night.and_then(Period::temperature),
+                    wind_speed: p.wind_speed(),
+                    wind_bearing: p.wind_bearing(),
+                    precip_probability: p.probability_of_precipitation.value(),
+                    precip_type,
+                    sunrise_time: sun.map(|(rise, _)| rise),
+                    sunset_time: sun.map(|(_, set)| set),
+                    moon_phase: Some(forecast::moon_phase(time)),
+                    ..DataPoint::default()
+                }
+            })
+            .collect();
+
+        let mut current = match r.observation {
+            Some(Feature { properties: o }) => {
+                let time = timestamp(&o.timestamp).unwrap_or_default();
+                let (icon, precip_type) = o
+                    .icon
+                    .as_ref()
+                    .map_or((None, None), |i| parse_icon(i, i.contains("/day/")));
+                let temperature = o.temperature.value();
+                let apparent_temperature = o
+                    .wind_chill
+                    .value()
+                    .or_else(|| o.heat_index.value())
+                    .or(temperature);
+                DataPoint {
+                    time,
+                    summary: o.text_description.clone(),
+                    icon,
+                    temperature,
+                    apparent_temperature,
+                    dew_point: o.dewpoint.value(),
+                    humidity: o.relative_humidity.value(),
+                    pressure: observed_pressure,
+                    wind_speed: o.wind_speed.value(),
+                    wind_gust: o.wind_gust.value(),
+                    wind_bearing: o.wind_direction.value(),
+                    visibility: o.visibility.value(),
+                    precip_type,
+                    moon_phase: Some(forecast::moon_phase(time)),
+                    ..DataPoint::default()
+                }
+            }
+            // Without a nearby station, use the first hour of the forecast.
+            None => hourly.first().cloned().unwrap_or_default(),
+        };
+        // The observation is usually from the hour before, which would leave the current hour
+        // unmarked, so it's now as of when the forecast was fetched.
+        if let Some(time) = r.time {
+            current.time = time;
+        }
+
+        let alerts = r
+            .alerts
+            .map(|a| a.features)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|Feature { properties: a }| Alert {
+                severity: Some(if a.event.ends_with("Warning") {
+                    Severity::Warning
+                } else if a.event.ends_with("Watch") {
+                    Severity::Watch
+                } else {
+                    match a.severity.as_deref() {
+                        Some("Extreme") | Some("Severe") => Severity::Warning,
+                        _ => Severity::Advisory,
+                    }
+                }),
+                title: a.headline.unwrap_or(a.event),
+                description: a.description.unwrap_or_default(),
+                time: timestamp(&a.sent).unwrap_or_default(),
+                expires: a.expires.as_ref().and_then(|e| timestamp(e)),
+                uri: a.id,
+                regions: a
+                    .area_desc
+                    .map(|areas| areas.split("; ").map(str::to_string).collect())
+                    .unwrap_or_default(),
+            })
+            .collect();
+
+        Self {
+            place: None,
+            latitude: lat,
+            longitude: lon,
+            timezone: r.time_zone,
+            // Forecast periods are given in local time, e.g. "2019-06-01T12:00:00-05:00".
+            utc_offset: r
+                .hourly
+                .properties
+                .periods
+                .first()
+                .and_then(|p| DateTime::parse_from_rfc3339(&p.start_time).ok())
+                .map(|t| t.offset().local_minus_utc()),
+            current,
+            hourly: DataBlock {
+                summary: None,
+                data: hourly,
+            },
+            daily: DataBlock {
+                summary: periods.first().and_then(|p| p.detailed_forecast.clone()),
+                data: daily,
+            },
+            alerts,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::DEFAULT_USER_AGENT;
+    use crate::test_server::{Route, TestServer};
+
+    fn server() -> TestServer {
+        TestServer::new(server_routes())
+    }
+
+    fn server_routes() -> Vec<Route> {
+        vec![
+            Route::new(
+                "/points/",
+                include_str!("../../tests/fixtures/nws/points.json"),
+            ),
+            Route::new(
+                "/gridpoints/TOP/32,81/stations",
+                include_str!("../../tests/fixtures/nws/stations.json"),
+            ),
+            Route::new(
+                "/gridpoints/TOP/32,81/forecast/hourly",
+                include_str!("../../tests/fixtures/nws/forecast-hourly.json"),
+            ),
+            Route::new(
+                "/gridpoints/TOP/32,81/forecast",
+                include_str!("../../tests/fixtures/nws/forecast.json"),
+            ),
+            Route::new(
+                "/stations/KMYZ/observations/latest",
+                include_str!("../../tests/fixtures/nws/observation.json"),
+            ),
+            Route::new(
+                "/alerts/active",
+                include_str!("../../tests/fixtures/nws/alerts.json"),
+            ),
+            // After the others, which it's the start of.
+            Route::new(
+                "/gridpoints/TOP/32,81",
+                include_str!("../../tests/fixtures/nws/gridpoints.json"),
+            ),
+        ]
+    }
+
+    fn request() -> Request {
+        Request {
+            lat: 39.7456,
+            lon: -97.0892,
+            ..Request::default()
+        }
+    }
+
+    #[test]
+    fn fetch_and_parse() {
+        let server = server();
+        let provider = Nws::new(&server.url, "weather-test", None);
+
+        let forecast = provider.parse(provider.fetch(&request()).unwrap()).unwrap();
+
+        let requests = server.requests();
+        assert!(requests[0].starts_with("GET /points/39.7456,-97.0892 "));
+        assert!(requests
+            .iter()
+            .all(|r| r.to_lowercase().contains("user-agent: weather-test")));
+
+        assert_eq!(Some("America/Chicago".to_string()), forecast.timezone);
+
+        let c = forecast.current;
+        assert!(Utc::now().timestamp() - c.time < 60);
+        assert_eq!(Some(22.2), c.temperature);
+        assert_eq!(Some(1016.9), c.pressure);
+        assert_eq!(Some(18.36), c.wind_speed.map(|s| (s * 100.).round() / 100.));
+        assert_eq!(Some(200.), c.wind_bearing);
+        assert_eq!(Some(Icon::PartlyCloudyDay), c.icon);
+
+        let h = forecast.hourly.data;
+        assert_eq!(3, h.len());
+        assert_eq!(Some(21.), h[0].temperature);
+        assert_eq!(Some(1015.), h[1].pressure);
+        assert_eq!(Some(1014.2), h[2].pressure);
+        assert_eq!(Some(20.), h[0].wind_speed);
+        assert_eq!(Some(202.5), h[0].wind_bearing);
+        assert_eq!(Some(0.4), h[2].precip_probability);
+        assert_eq!(Some(Icon::Thunderstorm), h[2].icon);
+
+        let d = forecast.daily.data;
+        assert_eq!(2, d.len());
+        assert_eq!(Some(29.), d[0].temperature_high);
+        assert_eq!(Some(17.), d[0].temperature_low);
+        assert!(d[0].sunrise_time.is_some());
+
+        assert_eq!(1, forecast.alerts.len());
+        assert_eq!(Some(Severity::Watch), forecast.alerts[0].severity);
+    }
+
+    #[test]
+    fn grid_lookup_is_cached() {
+        let server = server();
+        let dir = cache::test_dir("nws-grid-lookup");
+        let provider = Nws::new(&server.url, "weather-test", Some(dir));
+
+        provider.fetch(&request()).unwrap();
+        provider.fetch(&request()).unwrap();
+
+        let lookups = server
+            .requests()
+            .iter()
+            .filter(|r| r.starts_with("GET /points/") || r.contains("/stations "))
+            .count();
+        assert_eq!(2, lookups);
+    }
+
+    #[test]
+    fn grid_lookup_cached_per_server() {
+        let first = server();
+        let second = server();
+        let dir = cache::test_dir("nws-grid-lookup-per-server");
+
+        Nws::new(&first.url, "weather-test", Some(dir.clone()))
+            .fetch(&request())
+            .unwrap();
+        Nws::new(&second.url, "weather-test", Some(dir))
+            .fetch(&request())
+            .unwrap();
+
+        assert!(second
+            .requests()
+            .iter()
+            .any(|r| r.starts_with("GET /points/")));
+    }
+
+    #[test]
+    fn expired_grid_lookup() {
+        let server = server();
+        let dir = cache::test_dir("nws-expired-grid-lookup");
+        let provider = Nws::new(&server.url, "weather-test", Some(dir.clone()));
+
+        provider.fetch(&request()).unwrap();
+        let name = format!(
+            "nws-point-{}-39.7456,-97.0892.json",
+            cache::short_hash(&server.url)
+        );
+        let mut point: Point = cache::read(&dir, &name).unwrap();
+        point.fetched -= POINT_TTL;
+        cache::write(&dir, &name, &point).unwrap();
+        provider.fetch(&request()).unwrap();
+
+        let lookups = server
+            .requests()
+            .iter()
+            .filter(|r| r.starts_with("GET /points/"))
+            .count();
+        assert_eq!(2, lookups);
+    }
+
+    #[test]
+    fn without_observation_or_alerts() {
+        let server = TestServer::new(
+            server_routes()
+                .into_iter()
+                .filter(|r| !r.path.contains("observations") && !r.path.contains("alerts"))
+                .collect(),
+        );
+        let provider = Nws::new(&server.url, "weather-test", None);
+
+        let forecast = provider.parse(provider.fetch(&request()).unwrap()).unwrap();
+
+        // The first hour of the forecast stands in for the observation.
+        assert_eq!(Some(21.), forecast.current.temperature);
+        assert!(forecast.alerts.is_empty());
+    }
+
+    #[test]
+    fn without_stations() {
+        let server = TestServer::new(
+            server_routes()
+                .into_iter()
+                // The gridpoint data's path starts the same way.
+                .filter(|r| !r.path.ends_with("/stations") && r.path != "/gridpoints/TOP/32,81")
+                .collect(),
+        );
+        let dir = cache::test_dir("nws-without-stations");
+        let provider = Nws::new(&server.url, "weather-test", Some(dir));
+
+        let forecast = provider.parse(provider.fetch(&request()).unwrap()).unwrap();
+        assert_eq!(Some(21.), forecast.current.temperature);
+
+        // The lookup isn't cached without them.
+        provider.fetch(&request()).unwrap();
+        let lookups = server
+            .requests()
+            .iter()
+            .filter(|r| r.starts_with("GET /points/"))
+            .count();
+        assert_eq!(2, lookups);
+    }
+
+    #[test]
+    fn without_pressure_forecast() {
+        let server = TestServer::new(
+            server_routes()
+                .into_iter()
+                .filter(|r| r.path != "/gridpoints/TOP/32,81")
+                .collect(),
+        );
+        let provider = Nws::new(&server.url, "weather-test", None);
+
+        let forecast = provider.parse(provider.fetch(&request()).unwrap()).unwrap();
+
+        // The latest observation stands in for the forecast.
+        assert!(forecast
+            .hourly
+            .data
+            .iter()
+            .all(|h| h.pressure == Some(1016.9)));
+    }
+
+    #[test]
+    fn intervals() {
+        let start = 1_559_408_400;
+        assert_eq!(
+            Some((start, start + 2 * 60 * 60)),
+            interval("2019-06-01T17:00:00+00:00/PT2H")
+        );
+        assert_eq!(
+            Some((start, start + 30 * 60 * 60 + 30 * 60)),
+            interval("2019-06-01T17:00:00+00:00/P1DT6H30M")
+        );
+        assert_eq!(None, interval("2019-06-01T17:00:00+00:00"));
+        assert_eq!(None, interval("2019-06-01T17:00:00+00:00/PT2X"));
+    }
+
+    #[test]
+    fn historical_is_unsupported() {
+        let provider = Nws::new(API_URL, DEFAULT_USER_AGENT, None);
+        let request = Request {
+            time: Some("1559408400".to_string()),
+            ..request()
+        };
+        match provider.fetch(&request) {
+            Err(Error::Unsupported(_)) => {}
+            r => panic!("expected an unsupported error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn icon_urls() {
+        assert_eq!(
+            (Some(Icon::Thunderstorm), Some(PrecipType::Rain)),
+            parse_icon(
+                "https://api.weather.gov/icons/land/night/tsra_sct,40/rain,60?size=small",
+                false
+            )
+        );
+        assert_eq!(
+            (Some(Icon::ClearNight), None),
+            parse_icon(
+                "https://api.weather.gov/icons/land/night/few?size=small",
+                false
+            )
+        );
+        assert_eq!(
+            (None, None),
+            parse_icon("https://example.com/unknown", true)
+        );
+    }
+}
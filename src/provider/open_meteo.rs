@@ -0,0 +1,564 @@
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, Utc};
+use chrono_tz::Tz;
+use reqwest::Client;
+use serde_json::{json, Value};
+
+use super::{parse_time, Provider, Request};
+use crate::forecast::{self, DataBlock, DataPoint, Forecast, Icon, PrecipType};
+use crate::Result;
+
+pub const API_URL: &str = "https://api.open-meteo.com";
+pub const ARCHIVE_URL: &str = "https://archive-api.open-meteo.com";
+
+/// How many days back the forecast API keeps. Older days come from the archive instead.
+const PAST_DAYS: i64 = 92;
+
+const CURRENT: &str = "temperature_2m,apparent_temperature,relative_humidity_2m,dew_point_2m,\
+                       pressure_msl,precipitation,cloud_cover,wind_speed_10m,wind_gusts_10m,\
+                       wind_direction_10m,weather_code,is_day";
+const HOURLY: &str = "temperature_2m,apparent_temperature,relative_humidity_2m,dew_point_2m,\
+                      pressure_msl,precipitation,precipitation_probability,cloud_cover,\
+                      visibility,uv_index,wind_speed_10m,wind_gusts_10m,wind_direction_10m,\
+                      weather_code,is_day";
+const DAILY: &str = "weather_code,temperature_2m_max,temperature_2m_min,sunrise,sunset,\
+                     precipitation_sum,precipitation_probability_max,uv_index_max,\
+                     wind_speed_10m_max,wind_gusts_10m_max,wind_direction_10m_dominant";
+/// The archive doesn't have chances of precipitation, UV indexes or visibility.
+const ARCHIVE_HOURLY: &str = "temperature_2m,apparent_temperature,relative_humidity_2m,\
+                              dew_point_2m,pressure_msl,precipitation,cloud_cover,\
+                              wind_speed_10m,wind_gusts_10m,wind_direction_10m,weather_code,\
+                              is_day";
+const ARCHIVE_DAILY: &str = "weather_code,temperature_2m_max,temperature_2m_min,sunrise,sunset,\
+                             precipitation_sum,wind_speed_10m_max,wind_gusts_10m_max,\
+                             wind_direction_10m_dominant";
+
+pub struct OpenMeteo {
+    client: Client,
+    url: String,
+    archive_url: String,
+}
+
+/// The timezone Open-Meteo places a location in.
+#[derive(Debug, Deserialize)]
+struct Zone {
+    timezone: Option<String>,
+    #[serde(default)]
+    utc_offset_seconds: i32,
+}
+
+impl Zone {
+    /// The location's date at `time`.
+    fn date(&self, time: DateTime<Utc>) -> NaiveDate {
+        match self.timezone.as_ref().and_then(|tz| tz.parse::<Tz>().ok()) {
+            Some(tz) => time.with_timezone(&tz).naive_local().date(),
+            None => time
+                .with_timezone(&FixedOffset::east(self.utc_offset_seconds))
+                .naive_local()
+                .date(),
+        }
+    }
+}
+
+impl OpenMeteo {
+    pub fn new(url: &str) -> Self {
+        Self {
+            client: Client::new(),
+            url: url.trim_end_matches('/').to_string(),
+            archive_url: ARCHIVE_URL.to_string(),
+        }
+    }
+
+    /// Ask a different host for days older than the forecast API keeps.
+    pub fn archive_url(mut self, url: &str) -> Self {
+        self.archive_url = url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Look up the location's timezone, which historical requests need to ask for the right
+    /// day.
+    fn zone(&self, request: &Request) -> Result<Zone> {
+        let query = [
+            ("latitude", request.lat.to_string()),
+            ("longitude", request.lon.to_string()),
+            ("timezone", "auto".to_string()),
+            ("forecast_days", "1".to_string()),
+        ];
+
+        Ok(self
+            .client
+            .get(&format!("{}/v1/forecast", self.url))
+            .query(&query)
+            .send()?
+            .error_for_status()?
+            .json()?)
+    }
+}
+
+impl Provider for OpenMeteo {
+    fn name(&self) -> &'static str {
+        "open-meteo"
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn fetch(&self, request: &Request) -> Result<Value> {
+        let mut query = vec![
+            ("latitude", request.lat.to_string()),
+            ("longitude", request.lon.to_string()),
+            ("timezone", "auto".to_string()),
+            ("timeformat", "unixtime".to_string()),
+            ("wind_speed_unit", "kmh".to_string()),
+        ];
+
+        let time = match request.time {
+            Some(ref time) => Some(parse_time(time)?),
+            None => None,
+        };
+
+        let url = if let Some(time) = time {
+            // Days are the location's own, not the computer's.
+            let zone = self.zone(request)?;
+            let date = zone.date(time);
+            let date_param = date.format("%Y-%m-%d").to_string();
+            query.push(("start_date", date_param.clone()));
+            query.push(("end_date", date_param));
+
+            if date < zone.date(Utc::now()) - Duration::days(PAST_DAYS) {
+                query.push(("hourly", ARCHIVE_HOURLY.to_string()));
+                query.push(("daily", ARCHIVE_DAILY.to_string()));
+                format!("{}/v1/archive", self.archive_url)
+            } else {
+                query.push(("hourly", HOURLY.to_string()));
+                query.push(("daily", DAILY.to_string()));
+                format!("{}/v1/forecast", self.url)
+            }
+        } else {
+            let hours = if request.extend_hourly { 168 } else { 48 };
+            query.push(("current", CURRENT.to_string()));
+            query.push(("hourly", HOURLY.to_string()));
+            query.push(("daily", DAILY.to_string()));
+            query.push(("forecast_hours", hours.to_string()));
+            query.push(("forecast_days", "8".to_string()));
+            format!("{}/v1/forecast", self.url)
+        };
+
+        let mut response: Value = self
+            .client
+            .get(&url)
+            .query(&query)
+            .send()?
+            .error_for_status()?
+            .json()?;
+
+        // Current conditions are for now, not the time asked for, so that hour of the day stands
+        // in for them.
+        if let Some(time) = time {
+            response["requested_time"] = json!(time.timestamp());
+        }
+
+        Ok(response)
+    }
+
+    fn parse(&self, raw: Value) -> Result<Forecast> {
+        let response: Response = serde_json::from_value(raw)?;
+        Ok(response.into())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Response {
+    latitude: f64,
+    longitude: f64,
+    timezone: Option<String>,
+    utc_offset_seconds: Option<i32>,
+    current: Option<Current>,
+    /// The time given with `--historical`, if any.
+    requested_time: Option<i64>,
+    #[serde(default)]
+    hourly: Hourly,
+    #[serde(default)]
+    daily: Daily,
+}
+
+#[derive(Debug, Deserialize)]
+struct Current {
+    time: i64,
+    temperature_2m: Option<f64>,
+    apparent_temperature: Option<f64>,
+    relative_humidity_2m: Option<f64>,
+    dew_point_2m: Option<f64>,
+    pressure_msl: Option<f64>,
+    precipitation: Option<f64>,
+    cloud_cover: Option<f64>,
+    wind_speed_10m: Option<f64>,
+    wind_gusts_10m: Option<f64>,
+    wind_direction_10m: Option<f64>,
+    weather_code: Option<u8>,
+    is_day: Option<u8>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Hourly {
+    time: Vec<i64>,
+    temperature_2m: Vec<Option<f64>>,
+    apparent_temperature: Vec<Option<f64>>,
+    relative_humidity_2m: Vec<Option<f64>>,
+    dew_point_2m: Vec<Option<f64>>,
+    pressure_msl: Vec<Option<f64>>,
+    precipitation: Vec<Option<f64>>,
+    precipitation_probability: Vec<Option<f64>>,
+    cloud_cover: Vec<Option<f64>>,
+    visibility: Vec<Option<f64>>,
+    uv_index: Vec<Option<f64>>,
+    wind_speed_10m: Vec<Option<f64>>,
+    wind_gusts_10m: Vec<Option<f64>>,
+    wind_direction_10m: Vec<Option<f64>>,
+    weather_code: Vec<Option<u8>>,
+    is_day: Vec<Option<u8>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Daily {
+    time: Vec<i64>,
+    weather_code: Vec<Option<u8>>,
+    temperature_2m_max: Vec<Option<f64>>,
+    temperature_2m_min: Vec<Option<f64>>,
+    sunrise: Vec<Option<i64>>,
+    sunset: Vec<Option<i64>>,
+    precipitation_sum: Vec<Option<f64>>,
+    precipitation_probability_max: Vec<Option<f64>>,
+    uv_index_max: Vec<Option<f64>>,
+    wind_speed_10m_max: Vec<Option<f64>>,
+    wind_gusts_10m_max: Vec<Option<f64>>,
+    wind_direction_10m_dominant: Vec<Option<f64>>,
+}
+
+/// Get the `i`th value of a column, treating a short column the same as a missing value.
+fn at<T: Copy>(column: &[Option<T>], i: usize) -> Option<T> {
+    column.get(i).cloned().and_then(|v| v)
+}
+
+/// Convert a percentage to a fraction between 0 and 1.
+fn fraction(percent: Option<f64>) -> Option<f64> {
+    percent.map(|p| p / 100.)
+}
+
+impl From<Response> for Forecast {
+    fn from(r: Response) -> Self {
+        let h = r.hourly;
+        let hourly: Vec<DataPoint> = h
+            .time
+            .iter()
+            .enumerate()
+            .map(|(i, &time)| {
+                let (icon, summary, precip_type) =
+                    describe(at(&h.weather_code, i), at(&h.is_day, i));
+                DataPoint {
+                    time,
+                    summary,
+                    icon,
+                    temperature: at(&h.temperature_2m, i),
+                    apparent_temperature: at(&h.apparent_temperature, i),
+                    dew_point: at(&h.dew_point_2m, i),
+                    humidity: fraction(at(&h.relative_humidity_2m, i)),
+                    pressure: at(&h.pressure_msl, i),
+                    wind_speed: at(&h.wind_speed_10m, i),
+                    wind_gust: at(&h.wind_gusts_10m, i),
+                    wind_bearing: at(&h.wind_direction_10m, i),
+                    cloud_cover: fraction(at(&h.cloud_cover, i)),
+                    uv_index: at(&h.uv_index, i),
+                    // metres to kilometres
+                    visibility: at(&h.visibility, i).map(|v| v / 1000.),
+                    precip_intensity: at(&h.precipitation, i),
+                    precip_probability: fraction(at(&h.precipitation_probability, i)),
+                    precip_type,
+                    ..DataPoint::default()
+                }
+            })
+            .collect();
+
+        let current = match r.current {
+            Some(c) => {
+                let (icon, summary, precip_type) = describe(c.weather_code, c.is_day);
+                DataPoint {
+                    time: c.time,
+                    summary,
+                    icon,
+                    temperature: c.temperature_2m,
+                    apparent_temperature: c.apparent_temperature,
+                    dew_point: c.dew_point_2m,
+                    humidity: fraction(c.relative_humidity_2m),
+                    pressure: c.pressure_msl,
+                    wind_speed: c.wind_speed_10m,
+                    wind_gust: c.wind_gusts_10m,
+                    wind_bearing: c.wind_direction_10m,
+                    cloud_cover: fraction(c.cloud_cover),
+                    precip_intensity: c.precipitation,
+                    precip_type,
+                    moon_phase: Some(forecast::moon_phase(c.time)),
+                    ..DataPoint::default()
+                }
+            }
+            None => {
+                let time = r.requested_time.unwrap_or_default();
+                let hour = hourly
+                    .iter()
+                    .rev()
+                    .find(|d| d.time <= time)
+                    .or_else(|| hourly.first())
+                    .cloned()
+                    .unwrap_or_default();
+                DataPoint {
+                    time,
+                    moon_phase: Some(forecast::moon_phase(time)),
+                    ..hour
+                }
+            }
+        };
+
+        let d = r.daily;
+        let daily = d
+            .time
+            .iter()
+            .enumerate()
+            .map(|(i, &time)| {
+                let (icon, summary, precip_type) = describe(at(&d.weather_code, i), Some(1));
+                DataPoint {
+                    time,
+                    summary,
+                    icon,
+                    temperature_high: at(&d.temperature_2m_max, i),
+                    temperature_low: at(&d.temperature_2m_min, i),
+                    wind_speed: at(&d.wind_speed_10m_max, i),
+                    wind_gust: at(&d.wind_gusts_10m_max, i),
+                    wind_bearing: at(&d.wind_direction_10m_dominant, i),
+                    uv_index: at(&d.uv_index_max, i),
+                    // millimetres to centimetres
+                    precip_accumulation: at(&d.precipitation_sum, i).map(|p| p / 10.),
+                    precip_probability: fraction(at(&d.precipitation_probability_max, i)),
+                    precip_type,
+                    sunrise_time: at(&d.sunrise, i),
+                    sunset_time: at(&d.sunset, i),
+                    moon_phase: Some(forecast::moon_phase(time)),
+                    ..DataPoint::default()
+                }
+            })
+            .collect();
+
+        Self {
+            place: None,
+            latitude: r.latitude,
+            longitude: r.longitude,
+            timezone: r.timezone,
+            utc_offset: r.utc_offset_seconds,
+            current,
+            hourly: DataBlock {
+                summary: None,
+                data: hourly,
+            },
+            daily: DataBlock {
+                summary: None,
+                data: daily,
+            },
+            alerts: Vec::new(),
+        }
+    }
+}
+
+/// Describe a WMO weather interpretation code, as used by Open-Meteo.
+///
+/// See the "WMO Weather interpretation codes" table at https://open-meteo.com/en/docs.
+fn describe(
+    code: Option<u8>,
+    is_day: Option<u8>,
+) -> (Option<Icon>, Option<String>, Option<PrecipType>) {
+    let code = match code {
+        Some(c) => c,
+        None => return (None, None, None),
+    };
+    let day = is_day != Some(0);
+
+    let (icon, summary) = match code {
+        0 => (
+            if day {
+                Icon::ClearDay
+            } else {
+                Icon::ClearNight
+            },
+            "Clear",
+        ),
+        1 | 2 => (
+            if day {
+                Icon::PartlyCloudyDay
+            } else {
+                Icon::PartlyCloudyNight
+            },
+            if code == 1 {
+                "Mostly Clear"
+            } else {
+                "Partly Cloudy"
+            },
+        ),
+        3 => (Icon::Cloudy, "Overcast"),
+        45 | 48 => (Icon::Fog, "Foggy"),
+        51 | 53 | 55 => (Icon::Rain, "Drizzle"),
+        56 | 57 => (Icon::Sleet, "Freezing Drizzle"),
+        61 => (Icon::Rain, "Light Rain"),
+        63 => (Icon::Rain, "Rain"),
+        65 => (Icon::Rain, "Heavy Rain"),
+        66 | 67 => (Icon::Sleet, "Freezing Rain"),
+        71 => (Icon::Snow, "Light Snow"),
+        73 => (Icon::Snow, "Snow"),
+        75 => (Icon::Snow, "Heavy Snow"),
+        77 => (Icon::Snow, "Snow Grains"),
+        80..=82 => (Icon::Rain, "Rain Showers"),
+        85 | 86 => (Icon::Snow, "Snow Showers"),
+        95 => (Icon::Thunderstorm, "Thunderstorm"),
+        96 | 99 => (Icon::Hail, "Thunderstorm with Hail"),
+        _ => return (None, None, None),
+    };
+
+    let precip_type = match icon {
+        Icon::Rain | Icon::Thunderstorm => Some(PrecipType::Rain),
+        Icon::Sleet | Icon::Hail => Some(PrecipType::Sleet),
+        Icon::Snow => Some(PrecipType::Snow),
+        _ => None,
+    };
+
+    (Some(icon), Some(summary.to_string()), precip_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{Route, TestServer};
+
+    const FIXTURE: &str = include_str!("../../tests/fixtures/open-meteo.json");
+    const ARCHIVE_FIXTURE: &str = include_str!("../../tests/fixtures/open-meteo-archive.json");
+
+    #[test]
+    fn fetch_and_parse() {
+        let server = TestServer::new(vec![Route::new("/v1/forecast", FIXTURE)]);
+        let provider = OpenMeteo::new(&server.url);
+
+        let raw = provider
+            .fetch(&Request {
+                lat: 49.9,
+                lon: -97.14,
+                ..Request::default()
+            })
+            .unwrap();
+        let forecast = provider.parse(raw).unwrap();
+
+        let requests = server.requests();
+        assert_eq!(1, requests.len());
+        assert!(requests[0].contains("latitude=49.9"));
+        assert!(requests[0].contains("forecast_hours=48"));
+
+        assert_eq!(
+            Some("America/Winnipeg"),
+            forecast.timezone.as_ref().map(|s| &s[..])
+        );
+
+        let c = forecast.current;
+        assert_eq!(Some(-12.3), c.temperature);
+        assert_eq!(Some(-19.8), c.apparent_temperature);
+        assert_eq!(Some(1021.4), c.pressure);
+        assert_eq!(Some(18.4), c.wind_speed);
+        assert_eq!(Some(290.), c.wind_bearing);
+        assert_eq!(Some(0.78), c.humidity);
+        assert_eq!(Some(Icon::PartlyCloudyDay), c.icon);
+        assert_eq!(Some("Partly Cloudy"), c.summary.as_ref().map(|s| &s[..]));
+        assert!(c.moon_phase.is_some());
+
+        let h = forecast.hourly.data;
+        assert_eq!(4, h.len());
+        assert_eq!(
+            vec![Some(1021.0), Some(1021.4), None, Some(1022.6)],
+            h.iter().map(|d| d.pressure).collect::<Vec<_>>()
+        );
+        assert_eq!(Some(Icon::ClearNight), h[3].icon);
+        assert_eq!(Some(0.1), h[1].precip_probability);
+
+        let d = forecast.daily.data;
+        assert_eq!(2, d.len());
+        assert_eq!(Some(-8.1), d[0].temperature_high);
+        assert_eq!(Some(1_700_660_400), d[0].sunrise_time);
+        assert_eq!(Some(1_700_692_260), d[0].sunset_time);
+        assert_eq!(Some(PrecipType::Snow), d[1].precip_type);
+    }
+
+    #[test]
+    fn historical_day_in_location_timezone() {
+        let server = TestServer::new(vec![Route::new("/v1/forecast", FIXTURE)]);
+        let provider = OpenMeteo::new(&server.url);
+        // Late in the evening in Winnipeg, but already tomorrow in UTC.
+        let time = Utc::now().date().and_hms(3, 0, 0) - Duration::days(1);
+
+        provider
+            .fetch(&Request {
+                lat: 49.9,
+                lon: -97.14,
+                time: Some(time.timestamp().to_string()),
+                ..Request::default()
+            })
+            .unwrap();
+
+        let date = time
+            .with_timezone(&chrono_tz::America::Winnipeg)
+            .format("%Y-%m-%d");
+        let requests = server.requests();
+        assert_eq!(2, requests.len());
+        assert!(requests[1].starts_with("GET /v1/forecast?"));
+        assert!(requests[1].contains(&format!("start_date={}&end_date={}", date, date)));
+        assert!(!requests[1].contains("current="));
+    }
+
+    #[test]
+    fn historical_from_archive() {
+        let server = TestServer::new(vec![
+            Route::new("/v1/forecast", FIXTURE),
+            Route::new("/v1/archive", ARCHIVE_FIXTURE),
+        ]);
+        let provider = OpenMeteo::new(&server.url).archive_url(&server.url);
+
+        let raw = provider
+            .fetch(&Request {
+                lat: 49.9,
+                lon: -97.14,
+                time: Some("2019-06-01T03:20:00Z".to_string()),
+                ..Request::default()
+            })
+            .unwrap();
+        let forecast = provider.parse(raw).unwrap();
+
+        let requests = server.requests();
+        assert!(requests[1].starts_with("GET /v1/archive?"));
+        assert!(requests[1].contains("start_date=2019-05-31&end_date=2019-05-31"));
+        assert!(!requests[1].contains("precipitation_probability"));
+
+        let c = forecast.current;
+        assert_eq!(1_559_359_200, c.time);
+        assert_eq!(Some(1014.2), c.pressure);
+        assert_eq!(Some(Icon::Rain), c.icon);
+        assert_eq!(24, forecast.hourly.data.len());
+        assert_eq!(Some(18.), forecast.daily.data[0].temperature_high);
+    }
+
+    #[test]
+    fn fetch_error_status() {
+        let server = TestServer::new(vec![]);
+        let provider = OpenMeteo::new(&server.url);
+        assert!(provider.fetch(&Request::default()).is_err());
+    }
+
+    #[test]
+    fn unknown_weather_code() {
+        assert_eq!((None, None, None), describe(Some(42), Some(1)));
+        assert_eq!((None, None, None), describe(None, Some(1)));
+    }
+}
@@ -0,0 +1,284 @@
+//! The output of `--format json`.
+//!
+//! Unlike `--raw`, which passes the provider's response through untouched, this is our own
+//! schema, so scripts don't break when the provider changes. Any change that isn't adding a new
+//! field bumps [`VERSION`].
+//!
+//! Every value is in the units listed under `units`, and every time is an ISO 8601 timestamp in
+//! the location's timezone.
+
+use chrono::SecondsFormat;
+
+use crate::forecast::{self, Alert, DataBlock, DataPoint, Forecast, PrecipType, Severity};
+use crate::{find_closest_time_position, get_wind_bearing_icon, local_time};
+
+/// The version of the schema, increased whenever a field changes or goes away.
+pub const VERSION: u32 = 1;
+
+#[derive(Debug, Serialize)]
+pub struct Output {
+    pub version: u32,
+    pub units: Units,
+    pub location: Location,
+    pub current: Point,
+    /// The index of the current hour in `hourly.data`.
+    pub current_hour: Option<usize>,
+    pub hourly: Block,
+    pub daily: Block,
+    pub alerts: Vec<AlertOutput>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Units {
+    pub temperature: &'static str,
+    pub speed: &'static str,
+    pub bearing: &'static str,
+    pub pressure: &'static str,
+    pub precip_intensity: &'static str,
+    pub precip_accumulation: &'static str,
+    pub visibility: &'static str,
+    /// For probabilities, humidity, cloud cover and the moon phase.
+    pub fraction: &'static str,
+}
+
+impl Default for Units {
+    fn default() -> Self {
+        Self {
+            temperature: "°C",
+            speed: "km/h",
+            bearing: "°",
+            pressure: "hPa",
+            precip_intensity: "mm/h",
+            precip_accumulation: "cm",
+            visibility: "km",
+            fraction: "0-1",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Location {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub place: Option<String>,
+    pub timezone: Option<String>,
+    /// Seconds east of UTC.
+    pub utc_offset: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Block {
+    pub summary: Option<String>,
+    pub data: Vec<Point>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Point {
+    pub time: String,
+    pub summary: Option<String>,
+    pub icon: Option<&'static str>,
+    /// Whether the sun is up, if the sunrise and sunset for that day are known.
+    pub is_day: Option<bool>,
+    pub temperature: Option<f64>,
+    pub apparent_temperature: Option<f64>,
+    pub temperature_high: Option<f64>,
+    pub temperature_high_time: Option<String>,
+    pub temperature_low: Option<f64>,
+    pub temperature_low_time: Option<String>,
+    pub dew_point: Option<f64>,
+    pub humidity: Option<f64>,
+    pub pressure: Option<f64>,
+    pub wind_speed: Option<f64>,
+    pub wind_gust: Option<f64>,
+    pub wind_bearing: Option<f64>,
+    /// An arrow pointing where the wind is blowing to.
+    pub wind_arrow: Option<&'static str>,
+    pub cloud_cover: Option<f64>,
+    pub uv_index: Option<f64>,
+    pub visibility: Option<f64>,
+    pub precip_intensity: Option<f64>,
+    pub precip_probability: Option<f64>,
+    pub precip_accumulation: Option<f64>,
+    pub precip_type: Option<&'static str>,
+    pub sunrise_time: Option<String>,
+    pub sunset_time: Option<String>,
+    pub moon_phase: Option<f64>,
+    pub moon_phase_name: Option<&'static str>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AlertOutput {
+    pub title: String,
+    pub description: String,
+    pub time: String,
+    pub expires: Option<String>,
+    pub severity: Option<&'static str>,
+    pub uri: Option<String>,
+    pub regions: Vec<String>,
+}
+
+impl Output {
+    pub fn new(weather: &Forecast) -> Self {
+        let offset = weather.utc_offset;
+        let daily = &weather.daily.data;
+
+        let now = local_time(weather.current.time, offset);
+        let times: Vec<_> = weather
+            .hourly
+            .data
+            .iter()
+            .map(|d| local_time(d.time, offset))
+            .collect();
+
+        Self {
+            version: VERSION,
+            units: Units::default(),
+            location: Location {
+                latitude: weather.latitude,
+                longitude: weather.longitude,
+                place: weather.place.clone(),
+                timezone: weather.timezone.clone(),
+                utc_offset: now.offset().local_minus_utc(),
+            },
+            current: Point::new(&weather.current, offset, daily),
+            current_hour: find_closest_time_position(&now, &times),
+            hourly: Block::new(&weather.hourly, offset, daily),
+            daily: Block {
+                summary: weather.daily.summary.clone(),
+                data: daily.iter().map(|d| Point::new(d, offset, &[])).collect(),
+            },
+            alerts: weather
+                .active_alerts()
+                .into_iter()
+                .map(|a| AlertOutput::new(a, offset))
+                .collect(),
+        }
+    }
+}
+
+impl Block {
+    fn new(block: &DataBlock, offset: Option<i32>, daily: &[DataPoint]) -> Self {
+        Self {
+            summary: block.summary.clone(),
+            data: block
+                .data
+                .iter()
+                .map(|d| Point::new(d, offset, daily))
+                .collect(),
+        }
+    }
+}
+
+impl Point {
+    /// Convert `d`, using `daily` to tell whether it's day or night.
+    fn new(d: &DataPoint, offset: Option<i32>, daily: &[DataPoint]) -> Self {
+        let time = |t: Option<i64>| t.map(|t| iso8601(t, offset));
+
+        Self {
+            time: iso8601(d.time, offset),
+            summary: d.summary.clone(),
+            icon: d.icon.map(|i| i.name()),
+            is_day: forecast::is_day(d.time, daily),
+            temperature: d.temperature,
+            apparent_temperature: d.apparent_temperature,
+            temperature_high: d.temperature_high,
+            temperature_high_time: time(d.temperature_high_time),
+            temperature_low: d.temperature_low,
+            temperature_low_time: time(d.temperature_low_time),
+            dew_point: d.dew_point,
+            humidity: d.humidity,
+            pressure: d.pressure,
+            wind_speed: d.wind_speed,
+            wind_gust: d.wind_gust,
+            wind_bearing: d.wind_bearing,
+            wind_arrow: d
+                .wind_bearing
+                .map(|b| get_wind_bearing_icon(b.trunc() as u32)),
+            cloud_cover: d.cloud_cover,
+            uv_index: d.uv_index,
+            visibility: d.visibility,
+            precip_intensity: d.precip_intensity,
+            precip_probability: d.precip_probability,
+            precip_accumulation: d.precip_accumulation,
+            precip_type: d.precip_type.map(PrecipType::name),
+            sunrise_time: time(d.sunrise_time),
+            sunset_time: time(d.sunset_time),
+            moon_phase: d.moon_phase,
+            moon_phase_name: d.moon_phase.map(forecast::moon_phase_name),
+        }
+    }
+}
+
+impl AlertOutput {
+    fn new(a: &Alert, offset: Option<i32>) -> Self {
+        Self {
+            title: a.title.clone(),
+            description: a.description.clone(),
+            time: iso8601(a.time, offset),
+            expires: a.expires.map(|t| iso8601(t, offset)),
+            severity: a.severity.map(Severity::name),
+            uri: a.uri.clone(),
+            regions: a.regions.clone(),
+        }
+    }
+}
+
+/// Format `time` in the location's timezone.
+fn iso8601(time: i64, offset: Option<i32>) -> String {
+    local_time(time, offset).to_rfc3339_opts(SecondsFormat::Secs, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::darksky_forecast;
+    use serde_json::{json, Value};
+
+    fn output() -> Value {
+        serde_json::to_value(Output::new(&darksky_forecast())).unwrap()
+    }
+
+    #[test]
+    fn versioned_with_units() {
+        let output = output();
+        assert_eq!(json!(VERSION), output["version"]);
+        assert_eq!(json!("°C"), output["units"]["temperature"]);
+        assert_eq!(json!(-6 * 60 * 60), output["location"]["utc_offset"]);
+        assert_eq!(json!("America/Winnipeg"), output["location"]["timezone"]);
+    }
+
+    #[test]
+    fn timestamps_in_location_timezone() {
+        let output = output();
+        let time = output["current"]["time"].as_str().unwrap();
+        assert!(time.ends_with("-06:00"), "{}", time);
+        assert!(output["daily"]["data"][0]["sunrise_time"]
+            .as_str()
+            .unwrap()
+            .ends_with("-06:00"));
+    }
+
+    #[test]
+    fn derived_values() {
+        let output = output();
+        let current = &output["current"];
+        assert!(current["wind_arrow"].is_string());
+        assert!(current["is_day"].is_boolean());
+        assert!(output["current_hour"].is_u64());
+        assert!(output["daily"]["data"][0]["moon_phase_name"].is_string());
+        assert_eq!(Value::Null, output["daily"]["data"][0]["is_day"]);
+    }
+
+    #[test]
+    fn current_hour_in_location_timezone() {
+        // Half an hour off from UTC, so the location's hours start at half past in UTC.
+        let mut weather = darksky_forecast();
+        weather.utc_offset = Some(-(3 * 60 + 30) * 60);
+        for d in &mut weather.hourly.data {
+            d.time -= d.time % 3600 - 30 * 60;
+        }
+        weather.current.time = weather.hourly.data[2].time + 50 * 60;
+
+        assert_eq!(Some(2), Output::new(&weather).current_hour);
+    }
+}
@@ -0,0 +1,705 @@
+//! The forecast drawn as an SVG chart, for `weather chart --svg`.
+//!
+//! The same forecast and config always give the same file, so it can be compared with saved
+//! copies in tests: coordinates are rounded to a tenth of a pixel, and times are shown with the
+//! location's UTC offset, or in UTC when there isn't one, rather than the computer's timezone.
+
+use chrono::Timelike;
+use clap::ArgMatches;
+
+use crate::color::Color;
+use crate::forecast::{self, DataPoint, Forecast, Metric};
+use crate::{get_forecasts, local_time, Config, Error, Result};
+
+/// Room around the plot for the title and legend, the time axis, and the temperature and
+/// pressure axes.
+const MARGIN_TOP: f64 = 44.;
+const MARGIN_BOTTOM: f64 = 28.;
+const MARGIN_LEFT: f64 = 48.;
+const MARGIN_RIGHT: f64 = 64.;
+
+/// The default colours, from the Tomorrow Night palette, for the parts of the chart that no
+/// other colour in the config file stands for.
+const TEMPERATURE: u32 = 0xcc_66_66_00;
+const APPARENT_TEMPERATURE: u32 = 0xde_93_5f_00;
+const PRECIPITATION: u32 = 0x81_a2_be_00;
+const PRESSURE: u32 = 0xb2_94_bb_00;
+const NIGHT: u32 = 0x37_3b_41_00;
+const NOW: u32 = 0xf0_c6_74_00;
+const TEXT: u32 = 0x70_78_80_00;
+
+pub fn run(
+    config: &Config,
+    matches: &ArgMatches<'_>,
+    chart_matches: &ArgMatches<'_>,
+) -> Result<()> {
+    let size = (
+        value_t!(chart_matches, "width", u32)?,
+        value_t!(chart_matches, "height", u32)?,
+    );
+    check_size(size)?;
+    let daily = chart_matches.is_present("daily");
+
+    let forecasts = get_forecasts(config, matches)?;
+    let charts: Vec<(&str, &Forecast)> = forecasts
+        .iter()
+        .map(|(location, weather, _)| {
+            (weather.place.as_ref().unwrap_or(location).as_str(), weather)
+        })
+        .collect();
+    let svg = render(config, &charts, daily, size);
+
+    match chart_matches.value_of("svg") {
+        Some("-") | None => print!("{}", svg),
+        Some(path) => std::fs::write(path, svg)?,
+    }
+    Ok(())
+}
+
+/// Make sure the chart has room for its margins.
+fn check_size((width, height): (u32, u32)) -> Result<()> {
+    let min_width = (MARGIN_LEFT + MARGIN_RIGHT) as u32;
+    let min_height = (MARGIN_TOP + MARGIN_BOTTOM) as u32;
+    if width < min_width || height < min_height {
+        return Err(Error::ChartTooSmall(min_width, min_height));
+    }
+    Ok(())
+}
+
+/// A chart for each forecast, one above the other, each `width` by `height` pixels.
+fn render(config: &Config, charts: &[(&str, &Forecast)], daily: bool, size: (u32, u32)) -> String {
+    let (width, height) = size;
+    let colours = Colours::new(config);
+    let total = height * charts.len().max(1) as u32;
+
+    let mut svg = vec![format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+         viewBox=\"0 0 {w} {h}\" font-family=\"sans-serif\" font-size=\"11\">",
+        w = width,
+        h = total,
+    )];
+    if let Some(ref background) = colours.background {
+        svg.push(format!(
+            "<rect width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+            width, total, background
+        ));
+    }
+
+    for (i, (title, weather)) in charts.iter().enumerate() {
+        svg.push(format!(
+            "<g transform=\"translate(0,{})\">",
+            height * i as u32
+        ));
+        svg.extend(chart(
+            &colours,
+            title,
+            weather,
+            daily,
+            f64::from(width),
+            f64::from(height),
+        ));
+        svg.push("</g>".to_string());
+    }
+
+    svg.push("</svg>".to_string());
+    svg.join("\n") + "\n"
+}
+
+/// The colours from the `[svg]` section of the config file, as `#rrggbb`.
+struct Colours {
+    temperature: String,
+    apparent_temperature: String,
+    precipitation: String,
+    pressure: String,
+    night: String,
+    now: String,
+    text: String,
+    background: Option<String>,
+}
+
+impl Colours {
+    fn new(config: &Config) -> Self {
+        let svg = &config.svg;
+        let colour =
+            |c: Option<Color>, default: u32| c.unwrap_or_else(|| Color::from(default)).rgb_hex();
+
+        Self {
+            temperature: colour(svg.temperature, TEMPERATURE),
+            apparent_temperature: colour(svg.apparent_temperature, APPARENT_TEMPERATURE),
+            precipitation: colour(svg.precipitation, PRECIPITATION),
+            pressure: colour(svg.pressure, PRESSURE),
+            night: colour(svg.night, NIGHT),
+            // Now is highlighted like the current hour is in the terminal, unless it's set.
+            now: colour(svg.now.or_else(|| config.highlight.and_then(|h| h.fg)), NOW),
+            text: colour(svg.text, TEXT),
+            background: svg.background.map(Color::rgb_hex),
+        }
+    }
+}
+
+/// A line on the chart.
+struct Line {
+    label: &'static str,
+    values: Vec<Option<f64>>,
+    colour: String,
+    dash: Option<&'static str>,
+}
+
+/// Where things go on a chart, in pixels and seconds.
+struct Plot {
+    left: f64,
+    right: f64,
+    top: f64,
+    bottom: f64,
+    start: i64,
+    end: i64,
+}
+
+impl Plot {
+    fn x(&self, time: i64) -> f64 {
+        self.left
+            + (time - self.start) as f64 / (self.end - self.start) as f64 * (self.right - self.left)
+    }
+}
+
+/// A linear scale from values to pixels, widened to round numbers so it has tidy ticks.
+struct Scale {
+    min: f64,
+    max: f64,
+    step: f64,
+    from: f64,
+    to: f64,
+}
+
+impl Scale {
+    /// A scale covering `values`, from pixel `from` at the lowest to `to` at the highest.
+    fn new<I: Iterator<Item = f64>>(values: I, from: f64, to: f64) -> Option<Self> {
+        let (min, max) = values.fold(None, |range, v| match range {
+            Some((min, max)) => Some((v.min(min), v.max(max))),
+            None => Some((v, v)),
+        })?;
+
+        // Aim for about four steps of 1, 2 or 5 times a power of ten.
+        let rough = if max > min { (max - min) / 4. } else { 1. };
+        let magnitude = 10_f64.powf(rough.log10().floor());
+        let step = [1., 2., 5., 10.]
+            .iter()
+            .map(|m| m * magnitude)
+            .find(|&s| s >= rough)
+            .unwrap_or(10. * magnitude);
+
+        let low = (min / step).floor() * step;
+        let high = ((max / step).ceil() * step).max(low + step);
+        Some(Self {
+            min: low,
+            max: high,
+            step,
+            from,
+            to,
+        })
+    }
+
+    fn at(&self, value: f64) -> f64 {
+        self.from + (value - self.min) / (self.max - self.min) * (self.to - self.from)
+    }
+
+    fn ticks(&self) -> Vec<f64> {
+        let steps = ((self.max - self.min) / self.step).round() as usize;
+        (0..=steps)
+            .map(|i| self.min + i as f64 * self.step)
+            .collect()
+    }
+
+    fn label(&self, value: f64) -> String {
+        let precision = if self.step < 1. { 1 } else { 0 };
+        // Keep -0 from showing up.
+        format!("{:.*}", precision, value + 0.)
+    }
+}
+
+/// The elements of one location's chart.
+fn chart(
+    colours: &Colours,
+    title: &str,
+    weather: &Forecast,
+    daily: bool,
+    width: f64,
+    height: f64,
+) -> Vec<String> {
+    let data: &[DataPoint] = if daily {
+        &weather.daily.data
+    } else {
+        &weather.hourly.data
+    };
+
+    let mut svg = Vec::new();
+    if !title.is_empty() {
+        svg.push(format!(
+            "<text x=\"{}\" y=\"16\" font-size=\"13\" font-weight=\"bold\" fill=\"{}\">{}</text>",
+            n(MARGIN_LEFT),
+            colours.text,
+            escape(title)
+        ));
+    }
+    if data.is_empty() {
+        return svg;
+    }
+
+    // Leave half a step at either end, so the bars fit.
+    let step = if data.len() > 1 {
+        data[1].time - data[0].time
+    } else if daily {
+        24 * 60 * 60
+    } else {
+        60 * 60
+    };
+    let plot = Plot {
+        left: MARGIN_LEFT,
+        right: width - MARGIN_RIGHT,
+        top: MARGIN_TOP,
+        bottom: height - MARGIN_BOTTOM,
+        start: data[0].time - step / 2,
+        end: data[data.len() - 1].time + step / 2,
+    };
+    let times: Vec<i64> = data.iter().map(|d| d.time).collect();
+
+    let temperatures = if daily {
+        vec![
+            Line {
+                label: "high",
+                values: Metric::TemperatureHigh.values(data),
+                colour: colours.temperature.clone(),
+                dash: None,
+            },
+            Line {
+                label: "low",
+                values: Metric::TemperatureLow.values(data),
+                colour: colours.temperature.clone(),
+                dash: Some("6 3"),
+            },
+        ]
+    } else {
+        vec![
+            Line {
+                label: "temperature",
+                values: Metric::Temperature.values(data),
+                colour: colours.temperature.clone(),
+                dash: None,
+            },
+            Line {
+                label: "feels like",
+                values: Metric::ApparentTemperature.values(data),
+                colour: colours.apparent_temperature.clone(),
+                dash: None,
+            },
+        ]
+    };
+    let pressure = Line {
+        label: "pressure",
+        values: Metric::Pressure.values(data),
+        colour: colours.pressure.clone(),
+        dash: None,
+    };
+    let precipitation = Metric::PrecipProbability.values(data);
+
+    // The legend, under the title.
+    let mut x = plot.left;
+    let swatches = temperatures
+        .iter()
+        .map(|l| (l.label, &l.colour))
+        .chain(std::iter::once((
+            "chance of precipitation",
+            &colours.precipitation,
+        )))
+        .chain(std::iter::once((pressure.label, &pressure.colour)));
+    for (label, colour) in swatches {
+        svg.push(format!(
+            "<rect x=\"{}\" y=\"25\" width=\"12\" height=\"8\" fill=\"{}\"/>",
+            n(x),
+            colour
+        ));
+        svg.push(format!(
+            "<text x=\"{}\" y=\"33\" fill=\"{}\">{}</text>",
+            n(x + 16.),
+            colours.text,
+            label
+        ));
+        x += 16. + label.len() as f64 * 6. + 14.;
+    }
+
+    if !daily {
+        svg.extend(nights(colours, &plot, &weather.daily.data));
+    }
+
+    // The temperature axis on the left, with lines across the plot.
+    let temperature_scale = Scale::new(
+        temperatures
+            .iter()
+            .flat_map(|l| l.values.iter().filter_map(|v| *v)),
+        plot.bottom,
+        plot.top,
+    );
+    if let Some(ref scale) = temperature_scale {
+        for tick in scale.ticks() {
+            let y = n(scale.at(tick));
+            svg.push(format!(
+                "<line x1=\"{}\" y1=\"{y}\" x2=\"{}\" y2=\"{y}\" stroke=\"{}\" \
+                 stroke-opacity=\"0.2\"/>",
+                n(plot.left),
+                n(plot.right),
+                colours.text,
+                y = y
+            ));
+            svg.push(format!(
+                "<text x=\"{}\" y=\"{}\" text-anchor=\"end\" fill=\"{}\">{}°</text>",
+                n(plot.left - 6.),
+                n(scale.at(tick) + 4.),
+                colours.text,
+                scale.label(tick)
+            ));
+        }
+    }
+
+    // The pressure axis on the right.
+    let pressure_scale = Scale::new(
+        pressure.values.iter().filter_map(|v| *v),
+        plot.bottom,
+        plot.top,
+    );
+    if let Some(ref scale) = pressure_scale {
+        for tick in scale.ticks() {
+            svg.push(format!(
+                "<text x=\"{}\" y=\"{}\" fill=\"{}\">{}</text>",
+                n(plot.right + 6.),
+                n(scale.at(tick) + 4.),
+                colours.pressure,
+                scale.label(tick)
+            ));
+        }
+        svg.push(format!(
+            "<text x=\"{}\" y=\"{}\" fill=\"{}\">hPa</text>",
+            n(plot.right + 6.),
+            n(plot.top - 8.),
+            colours.pressure
+        ));
+    }
+
+    // The chance of precipitation, as bars up from the bottom.
+    let bar_width = (plot.x(plot.start + step) - plot.x(plot.start)) * 0.6;
+    let precipitation_scale = Scale {
+        min: 0.,
+        max: 1.,
+        step: 0.25,
+        from: plot.bottom,
+        to: plot.top,
+    };
+    for (&time, p) in times.iter().zip(&precipitation) {
+        if let Some(p) = p.filter(|&p| p > 0.) {
+            let y = precipitation_scale.at(p);
+            svg.push(format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" \
+                 fill-opacity=\"0.5\"/>",
+                n(plot.x(time) - bar_width / 2.),
+                n(y),
+                n(bar_width),
+                n(plot.bottom - y),
+                colours.precipitation
+            ));
+        }
+    }
+
+    if let Some(ref scale) = pressure_scale {
+        svg.extend(line(&pressure, &times, &plot, scale));
+    }
+    if let Some(ref scale) = temperature_scale {
+        for l in &temperatures {
+            svg.extend(line(l, &times, &plot, scale));
+        }
+    }
+
+    // The time axis.
+    svg.push(format!(
+        "<line x1=\"{}\" y1=\"{y}\" x2=\"{}\" y2=\"{y}\" stroke=\"{}\"/>",
+        n(plot.left),
+        n(plot.right),
+        colours.text,
+        y = n(plot.bottom)
+    ));
+    // Every six hours over two days, and otherwise every day.
+    let every = if data.len() > 48 { 24 } else { 6 };
+    for &time in &times {
+        let local = local_time(time, Some(weather.utc_offset.unwrap_or(0)));
+        let format = match (daily || local.hour() == 0, local.hour() % every) {
+            (true, _) => "%a",
+            (false, 0) => "%H:%M",
+            _ => continue,
+        };
+        let label = local.format(format).to_string();
+        let x = n(plot.x(time));
+        svg.push(format!(
+            "<line x1=\"{x}\" y1=\"{}\" x2=\"{x}\" y2=\"{}\" stroke=\"{}\"/>",
+            n(plot.bottom),
+            n(plot.bottom + 4.),
+            colours.text,
+            x = x
+        ));
+        svg.push(format!(
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\" fill=\"{}\">{}</text>",
+            x,
+            n(plot.bottom + 16.),
+            colours.text,
+            label
+        ));
+    }
+
+    // Now, if it's on the chart.
+    let now = weather.current.time;
+    if plot.start <= now && now <= plot.end {
+        let x = n(plot.x(now));
+        svg.push(format!(
+            "<line x1=\"{x}\" y1=\"{}\" x2=\"{x}\" y2=\"{}\" stroke=\"{}\" stroke-width=\"1.5\"/>",
+            n(plot.top),
+            n(plot.bottom),
+            colours.now,
+            x = x
+        ));
+        svg.push(format!(
+            "<text x=\"{}\" y=\"{}\" fill=\"{}\">now</text>",
+            n(plot.x(now) + 3.),
+            n(plot.top + 10.),
+            colours.now
+        ));
+    }
+
+    svg
+}
+
+/// Shading over the nights, from each sunset to the next sunrise.
+fn nights(colours: &Colours, plot: &Plot, daily: &[DataPoint]) -> Vec<String> {
+    let mut edges: Vec<i64> = daily
+        .iter()
+        .flat_map(|d| d.sunrise_time.into_iter().chain(d.sunset_time))
+        .filter(|&t| plot.start < t && t < plot.end)
+        .collect();
+    edges.push(plot.start);
+    edges.push(plot.end);
+    edges.sort();
+    edges.dedup();
+
+    edges
+        .windows(2)
+        .filter(|w| forecast::is_day((w[0] + w[1]) / 2, daily) == Some(false))
+        .map(|w| {
+            format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" \
+                 fill-opacity=\"0.3\"/>",
+                n(plot.x(w[0])),
+                n(plot.top),
+                n(plot.x(w[1]) - plot.x(w[0])),
+                n(plot.bottom - plot.top),
+                colours.night
+            )
+        })
+        .collect()
+}
+
+/// A line through each value, broken where there isn't one. Lone values are drawn as dots.
+fn line(line: &Line, times: &[i64], plot: &Plot, scale: &Scale) -> Vec<String> {
+    let mut runs: Vec<Vec<(f64, f64)>> = vec![Vec::new()];
+    for (&time, value) in times.iter().zip(&line.values) {
+        match value {
+            Some(v) => runs
+                .last_mut()
+                .expect("there's always a run")
+                .push((plot.x(time), scale.at(*v))),
+            None => runs.push(Vec::new()),
+        }
+    }
+
+    let dash = line
+        .dash
+        .map(|d| format!(" stroke-dasharray=\"{}\"", d))
+        .unwrap_or_default();
+    runs.into_iter()
+        .filter(|run| !run.is_empty())
+        .map(|run| {
+            if run.len() == 1 {
+                let (x, y) = run[0];
+                return format!(
+                    "<circle cx=\"{}\" cy=\"{}\" r=\"2\" fill=\"{}\"/>",
+                    n(x),
+                    n(y),
+                    line.colour
+                );
+            }
+            let points: Vec<String> = run
+                .iter()
+                .map(|&(x, y)| format!("{},{}", n(x), n(y)))
+                .collect();
+            format!(
+                "<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"2\"{} points=\"{}\"/>",
+                line.colour,
+                dash,
+                points.join(" ")
+            )
+        })
+        .collect()
+}
+
+/// A coordinate, rounded to a tenth of a pixel.
+fn n(value: f64) -> String {
+    format!("{}", (value * 10.).round() / 10. + 0.)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forecast::DataPoint;
+    use crate::test_server::darksky_forecast;
+
+    const SIZE: (u32, u32) = (640, 240);
+
+    /// Compare with a saved chart. When the chart is meant to change, save the new one with
+    /// `UPDATE_GOLDEN=1 cargo test`, and look it over before committing it.
+    fn golden(name: &str, expected: &str, svg: &str) {
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("tests/fixtures")
+                .join(name);
+            std::fs::write(path, svg).unwrap();
+            return;
+        }
+        assert_eq!(expected, svg, "{} has changed", name);
+    }
+
+    #[test]
+    fn hourly_chart() {
+        let weather = darksky_forecast();
+        let svg = render(&Config::default(), &[("Winnipeg", &weather)], false, SIZE);
+        golden(
+            "chart-hourly.svg",
+            include_str!("../tests/fixtures/chart-hourly.svg"),
+            &svg,
+        );
+    }
+
+    #[test]
+    fn daily_chart() {
+        let config: Config = toml::from_str(
+            r##"
+            [svg]
+            temperature = "#ff000000"
+            background = "#ffffff00"
+            "##,
+        )
+        .unwrap();
+        let weather = darksky_forecast();
+        let svg = render(&config, &[("Winnipeg & area", &weather)], true, SIZE);
+        golden(
+            "chart-daily.svg",
+            include_str!("../tests/fixtures/chart-daily.svg"),
+            &svg,
+        );
+    }
+
+    #[test]
+    fn same_every_time() {
+        let weather = darksky_forecast();
+        let charts = [("Winnipeg", &weather), ("Selkirk", &weather)];
+        let svg = render(&Config::default(), &charts, false, SIZE);
+
+        assert_eq!(svg, render(&Config::default(), &charts, false, SIZE));
+        assert!(svg.contains("height=\"480\""));
+        assert!(svg.contains("<g transform=\"translate(0,240)\">"));
+    }
+
+    #[test]
+    fn nights_shaded() {
+        let hour = 60 * 60;
+        let start = 1_559_365_200;
+        let weather = Forecast {
+            hourly: forecast::DataBlock {
+                data: (0..24)
+                    .map(|i| DataPoint {
+                        time: start + i * hour,
+                        temperature: Some(10. + i as f64),
+                        ..DataPoint::default()
+                    })
+                    .collect(),
+                ..forecast::DataBlock::default()
+            },
+            daily: forecast::DataBlock {
+                data: vec![DataPoint {
+                    time: start,
+                    sunrise_time: Some(start + 5 * hour),
+                    sunset_time: Some(start + 21 * hour),
+                    ..DataPoint::default()
+                }],
+                ..forecast::DataBlock::default()
+            },
+            ..Forecast::default()
+        };
+
+        let colours = Colours::new(&Config::default());
+        let plot = Plot {
+            left: 0.,
+            right: 240.,
+            top: 0.,
+            bottom: 100.,
+            start,
+            end: start + 24 * hour,
+        };
+        let nights = nights(&colours, &plot, &weather.daily.data);
+        assert_eq!(2, nights.len());
+        assert!(nights[0].starts_with("<rect x=\"0\" y=\"0\" width=\"50\""));
+        assert!(nights[1].starts_with("<rect x=\"210\" y=\"0\" width=\"30\""));
+
+        // Without pressure, there's no axis for it.
+        let svg = render(&Config::default(), &[("Here", &weather)], false, SIZE);
+        assert!(!svg.contains("hPa"));
+        assert_eq!(1, svg.matches("<polyline").count());
+
+        // Without an offset, the times are in UTC.
+        assert!(svg.contains(">06:00</text>"));
+        assert!(svg.contains(">Sun</text>"));
+    }
+
+    #[test]
+    fn now_is_highlighted() {
+        let config: Config = toml::from_str(
+            r##"
+            [highlight]
+            fg = "#00ff00ff"
+            "##,
+        )
+        .unwrap();
+        assert_eq!("#00ff00", Colours::new(&config).now);
+        assert_eq!("#f0c674", Colours::new(&Config::default()).now);
+    }
+
+    #[test]
+    fn too_small() {
+        assert!(check_size(SIZE).is_ok());
+        assert!(check_size((112, 72)).is_ok());
+        match check_size((100, 300)) {
+            Err(Error::ChartTooSmall(112, 72)) => {}
+            r => panic!("expected the chart to be too small, got {:?}", r),
+        }
+        assert!(check_size((800, 50)).is_err());
+    }
+
+    #[test]
+    fn rounded_coordinates() {
+        assert_eq!("12.3", n(12.34));
+        assert_eq!("0", n(-0.01));
+        assert_eq!("100", n(100.));
+        assert_eq!("Tom &amp; Jerry &lt;3", escape("Tom & Jerry <3"));
+    }
+}
@@ -0,0 +1,465 @@
+//! Forecasts laid out in columns, for the `hourly` and `daily` subcommands.
+
+use clap::ArgMatches;
+use weather_icons::{DripIcon, Moon, Time};
+
+use crate::config::IconStyle;
+use crate::forecast::{self, DataPoint, Forecast, Icon, Metric, PrecipType};
+use crate::graph::Highlight;
+use crate::{
+    draw_chart, extend_hourly, find_closest_time_position, get_condition_icon, get_forecasts,
+    get_symbol_code_condition, get_wind_bearing_icon, local_time, Config, Result,
+};
+
+/// How many hours to show without `--hours`.
+const DEFAULT_HOURS: usize = 24;
+/// How many hours to show with `--extend`.
+const EXTENDED_HOURS: usize = 168;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Align {
+    Left,
+    Right,
+}
+
+/// Rows of text, padded so the columns line up.
+#[derive(Debug)]
+struct Table {
+    align: Vec<Align>,
+    /// The header, then each row.
+    rows: Vec<Vec<String>>,
+    /// The row to mark, not counting the header.
+    marked: Option<usize>,
+}
+
+impl Table {
+    fn new(header: &[&str], align: &[Align]) -> Self {
+        Self {
+            align: align.to_vec(),
+            rows: vec![header.iter().map(|h| h.to_string()).collect()],
+            marked: None,
+        }
+    }
+
+    fn row(&mut self, cells: Vec<String>) -> &mut Self {
+        self.rows.push(cells);
+        self
+    }
+
+    fn mark(&mut self, row: Option<usize>) -> &mut Self {
+        self.marked = row;
+        self
+    }
+
+    /// Lay out the table, with an arrow by the marked row, also styled with `highlight`.
+    fn render(&self, highlight: Option<&Highlight>) -> String {
+        let widths: Vec<usize> = (0..self.align.len())
+            .map(|i| {
+                self.rows
+                    .iter()
+                    .map(|row| row[i].chars().count())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        self.rows
+            .iter()
+            .enumerate()
+            .map(|(i, row)| {
+                let cells: Vec<String> = row
+                    .iter()
+                    .zip(&widths)
+                    .zip(&self.align)
+                    .map(|((cell, width), align)| {
+                        let padding = " ".repeat(width - cell.chars().count());
+                        match align {
+                            Align::Left => format!("{}{}", cell, padding),
+                            Align::Right => format!("{}{}", padding, cell),
+                        }
+                    })
+                    .collect();
+
+                let marked = i > 0 && self.marked == Some(i - 1);
+                let line = format!(
+                    "{} {}",
+                    if marked { "\u{25b8}" } else { " " },
+                    cells.join("  ").trim_end()
+                );
+                match highlight {
+                    Some(h) if marked => h.style().paint(line).to_string(),
+                    _ => line,
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+pub fn hourly(
+    config: &Config,
+    matches: &ArgMatches<'_>,
+    hourly_matches: &ArgMatches<'_>,
+) -> Result<()> {
+    let hours = if hourly_matches.is_present("hours") {
+        value_t!(hourly_matches, "hours", usize)?
+    } else if extend_hourly(matches) {
+        EXTENDED_HOURS
+    } else {
+        DEFAULT_HOURS
+    };
+
+    let metrics = chart_metrics(hourly_matches)?;
+
+    print_tables(config, matches, |weather| {
+        let data = &weather.hourly.data[..hours.min(weather.hourly.data.len())];
+        let times: Vec<_> = data
+            .iter()
+            .map(|d| local_time(d.time, weather.utc_offset))
+            .collect();
+        let position = find_closest_time_position(
+            &local_time(weather.current.time, weather.utc_offset),
+            &times,
+        );
+        let charts = charts(config, &metrics, weather, data, false, position);
+
+        Ok(charts + &hourly_table(config, weather, hours))
+    })
+}
+
+pub fn daily(
+    config: &Config,
+    matches: &ArgMatches<'_>,
+    daily_matches: &ArgMatches<'_>,
+) -> Result<()> {
+    let metrics = chart_metrics(daily_matches)?;
+
+    print_tables(config, matches, |weather| {
+        let charts = charts(config, &metrics, weather, &weather.daily.data, true, None);
+        Ok(charts + &daily_table(config, weather)?)
+    })
+}
+
+/// What `--chart` asks to draw above the table.
+fn chart_metrics(matches: &ArgMatches<'_>) -> Result<Vec<Metric>> {
+    if matches.is_present("chart") {
+        Ok(values_t!(matches, "chart", Metric)?)
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+/// A chart of each metric over the hourly or `daily` forecast `data`, from `weather`, with a
+/// blank line after each one to set it apart from the table.
+fn charts(
+    config: &Config,
+    metrics: &[Metric],
+    weather: &Forecast,
+    data: &[DataPoint],
+    daily: bool,
+    position: Option<usize>,
+) -> String {
+    metrics
+        .iter()
+        .map(|metric| {
+            let chart = draw_chart(
+                config,
+                weather,
+                *metric,
+                data,
+                daily,
+                position,
+                config.highlight.as_ref(),
+            );
+            format!("{}:\n{}\n\n", metric.description(), chart)
+        })
+        .collect()
+}
+
+/// Print a table for each location, under its name if there's more than one.
+fn print_tables<F>(config: &Config, matches: &ArgMatches<'_>, table: F) -> Result<()>
+where
+    F: Fn(&Forecast) -> Result<String>,
+{
+    let forecasts = get_forecasts(config, matches)?;
+    let several = forecasts.len() > 1;
+
+    let tables: Vec<String> = forecasts
+        .iter()
+        .map(|(location, weather, _)| {
+            let table = table(weather)?;
+            Ok(if several {
+                let name = weather.place.as_ref().unwrap_or(location);
+                format!("{}\n{}", name, table)
+            } else {
+                table
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    println!("{}", tables.join("\n\n"));
+    Ok(())
+}
+
+/// The first `hours` of the hourly forecast, with the current hour marked.
+fn hourly_table(config: &Config, weather: &Forecast, hours: usize) -> String {
+    use self::Align::*;
+
+    let data = &weather.hourly.data;
+    let daily = &weather.daily.data;
+
+    let times: Vec<_> = data
+        .iter()
+        .map(|d| local_time(d.time, weather.utc_offset))
+        .collect();
+    let position = find_closest_time_position(
+        &local_time(weather.current.time, weather.utc_offset),
+        &times,
+    );
+
+    let mut table = Table::new(
+        &[
+            "Time", "", "Temp", "Feels", "Precip", "", "Wind", "", "Pressure", "Humidity",
+        ],
+        &[
+            Left, Left, Right, Right, Right, Right, Right, Left, Right, Right,
+        ],
+    );
+
+    for d in data.iter().take(hours) {
+        table.row(vec![
+            local_time(d.time, weather.utc_offset)
+                .format("%a %H:%M")
+                .to_string(),
+            icon(config, d, forecast::is_day(d.time, daily)),
+            number(d.temperature, 0, "°"),
+            number(d.apparent_temperature, 0, "°"),
+            percent(d.precip_probability),
+            number(d.precip_intensity, 1, " mm/h"),
+            number(d.wind_speed, 0, " km/h"),
+            d.wind_bearing
+                .map(|b| get_wind_bearing_icon(b.trunc() as u32).to_string())
+                .unwrap_or_default(),
+            number(d.pressure, 0, " hPa"),
+            percent(d.humidity),
+        ]);
+    }
+    table.mark(position.filter(|&p| p < hours));
+
+    table.render(config.highlight.as_ref())
+}
+
+/// Each day of the daily forecast, with today marked.
+fn daily_table(config: &Config, weather: &Forecast) -> Result<String> {
+    use self::Align::*;
+
+    let time = |t: Option<i64>| {
+        t.map(|t| {
+            local_time(t, weather.utc_offset)
+                .format("%H:%M")
+                .to_string()
+        })
+        .unwrap_or_default()
+    };
+
+    let mut table = Table::new(
+        &[
+            "Day", "", "High", "", "Low", "", "Precip", "", "", "Sunrise", "Sunset", "Moon", "UV",
+            "Wind", "",
+        ],
+        &[
+            Left, Left, Right, Left, Right, Left, Left, Right, Right, Left, Left, Left, Right,
+            Right, Left,
+        ],
+    );
+
+    for d in &weather.daily.data {
+        let moon = match d.moon_phase {
+            Some(phase) => Moon::new(phase, config.moon_style)?.to_string(),
+            None => String::new(),
+        };
+
+        table.row(vec![
+            local_time(d.time, weather.utc_offset)
+                .format("%a")
+                .to_string(),
+            icon(config, d, Some(true)),
+            number(d.temperature_high, 0, "°"),
+            time(d.temperature_high_time),
+            number(d.temperature_low, 0, "°"),
+            time(d.temperature_low_time),
+            d.precip_type
+                .map(PrecipType::name)
+                .unwrap_or_default()
+                .to_string(),
+            percent(d.precip_probability),
+            number(d.precip_accumulation, 1, " cm"),
+            time(d.sunrise_time),
+            time(d.sunset_time),
+            moon,
+            number(d.uv_index, 0, ""),
+            number(d.wind_speed, 0, " km/h"),
+            d.wind_bearing
+                .map(|b| get_wind_bearing_icon(b.trunc() as u32).to_string())
+                .unwrap_or_default(),
+        ]);
+    }
+    // Dark Sky's days start at midnight in the location's timezone, which might not be ours.
+    let now = weather.current.time;
+    table.mark(
+        weather
+            .daily
+            .data
+            .iter()
+            .position(|d| d.time <= now && now < d.time + 24 * 60 * 60),
+    );
+
+    Ok(table.render(config.highlight.as_ref()))
+}
+
+/// The condition icon in the configured style. MET Norway's symbol codes say whether it's day
+/// or night themselves. Otherwise it's for day or night if that's known, and however the
+/// provider described it if not.
+fn icon(config: &Config, d: &DataPoint, day: Option<bool>) -> String {
+    let symbol = d
+        .symbol_code
+        .as_ref()
+        .and_then(|s| get_symbol_code_condition(s));
+    let time = |day: bool| if day { Time::Day } else { Time::Night };
+
+    let icon = match (symbol, d.icon) {
+        (Some((condition, t)), _) => condition
+            .variant(t.unwrap_or_else(|| time(day.unwrap_or(true))))
+            .unwrap_or_default(),
+        (None, Some(i)) => {
+            let day = day.unwrap_or(i != Icon::ClearNight && i != Icon::PartlyCloudyNight);
+            get_condition_icon(i, time(day))
+        }
+        (None, None) => return String::new(),
+    };
+    match config.icon_style.unwrap_or_default() {
+        IconStyle::WeatherIcons => icon.to_string(),
+        IconStyle::Dripicons => DripIcon::from(icon).to_string(),
+    }
+}
+
+fn number(value: Option<f64>, precision: usize, unit: &str) -> String {
+    value
+        .map(|v| format!("{:.*}{}", precision, v, unit))
+        .unwrap_or_default()
+}
+
+fn percent(fraction: Option<f64>) -> String {
+    number(fraction.map(|f| f * 100.), 0, "%")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::darksky_forecast;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn columns_line_up() {
+        let mut table = Table::new(&["Name", "Temp"], &[Align::Left, Align::Right]);
+        table
+            .row(vec!["Winnipeg".to_string(), "-30°".to_string()])
+            .row(vec!["Toronto".to_string(), "5°".to_string()])
+            .mark(Some(1));
+
+        assert_eq!(
+            "  Name      Temp\n  Winnipeg  -30°\n\u{25b8} Toronto     5°",
+            table.render(None)
+        );
+    }
+
+    #[test]
+    fn hourly_rows() {
+        let weather = darksky_forecast();
+        let table = hourly_table(&Config::default(), &weather, 3);
+        let lines: Vec<_> = table.lines().collect();
+
+        assert_eq!(4, lines.len());
+        assert!(lines[0].contains("Feels"));
+        assert!(lines[0].contains("Humidity"));
+        assert!(lines[1].contains("hPa"));
+
+        // The forecast is from 11:00, the second hour.
+        assert!(lines[2].starts_with('\u{25b8}'), "{}", table);
+        assert_eq!(1, table.matches('\u{25b8}').count());
+    }
+
+    #[test]
+    fn daily_rows() {
+        let weather = darksky_forecast();
+        let table = daily_table(&Config::default(), &weather).unwrap();
+        let lines: Vec<_> = table.lines().collect();
+
+        assert_eq!(weather.daily.data.len() + 1, lines.len());
+        assert!(lines[0].contains("Sunrise"));
+        assert!(lines[1].starts_with('\u{25b8}'), "{}", table);
+        assert!(lines[1].contains("rain"));
+        assert!(lines[1].contains("45%"));
+    }
+
+    #[test]
+    fn charts_above_table() {
+        let weather = darksky_forecast();
+        let data = &weather.hourly.data;
+        let charts = charts(
+            &Config::default(),
+            &[Metric::Temperature, Metric::Pressure],
+            &weather,
+            data,
+            false,
+            Some(1),
+        );
+        let lines: Vec<_> = charts.lines().collect();
+
+        assert_eq!("temperature:", lines[0]);
+        assert!(lines[1].contains('\u{2524}'), "{}", charts);
+        assert_eq!(2, charts.matches(":\n").count());
+        assert!(charts.ends_with("\n\n"));
+        assert!(charts.contains("pressure:"));
+    }
+
+    #[test]
+    fn block_charts() {
+        let config: Config = toml::from_str("chart_style = \"blocks\"\nchart_height = 2").unwrap();
+        let weather = darksky_forecast();
+        let charts = charts(
+            &config,
+            &[Metric::Pressure],
+            &weather,
+            &weather.hourly.data,
+            false,
+            None,
+        );
+        let lines: Vec<_> = charts.lines().collect();
+
+        assert_eq!(6, lines.len(), "{}", charts);
+        assert!(lines[1].starts_with("1012.60 hPa \u{2524}"));
+        assert!(lines[1].contains('\u{2588}'));
+        assert!(lines[2].starts_with("1011.70 hPa \u{2524}"));
+    }
+
+    #[test]
+    fn times_at_location() {
+        let mut weather = darksky_forecast();
+        weather.utc_offset = Some(0);
+        let table = hourly_table(&Config::default(), &weather, 1);
+
+        let first = Utc.timestamp(weather.hourly.data[0].time, 0);
+        assert!(
+            table.contains(&first.format("%a %H:%M").to_string()),
+            "{}",
+            table
+        );
+    }
+
+    #[test]
+    fn current_hour_left_out() {
+        let table = hourly_table(&Config::default(), &darksky_forecast(), 1);
+        assert!(!table.contains('\u{25b8}'));
+    }
+}
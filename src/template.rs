@@ -0,0 +1,470 @@
+//! User-defined output formats, e.g. `"{icon} {temp:.0}{deg} {summary} {graph:pressure}"`.
+//!
+//! Placeholders are written in braces, optionally followed by a colon and a format:
+//!
+//! - `{temperature}`, `{summary}`, ...: any field of the current conditions, by the names used
+//!   in [`DataPoint`], or `temp` and `feels_like` for short. The same fields are available from
+//!   other data points as `{current.<field>}`, `{today.<field>}`, `{hourly.<n>.<field>}` and
+//!   `{daily.<n>.<field>}`, counting from 0.
+//! - `{hourly.summary}` and `{daily.summary}`.
+//! - `{graph:<field>}` and `{graph:daily.<field>}`: a graph of an hourly or daily field.
+//! - `{icon}`, `{moon}`, `{wind_arrow}` and `{pressure_icon}`.
+//! - `{deg}` for a degree sign, `{place}` for the name of the location, `{stale}` for the
+//!   marker shown when the forecast is old, and `{alerts}` for the number of weather alerts.
+//!
+//! Numbers take a precision, like `{temp:.0}`, and times take a `strftime` format, like
+//! `{today.sunrise_time:%H:%M}`. Write `{{` and `}}` for literal braces.
+
+use chrono::format::{Item, StrftimeItems};
+use std::str::FromStr;
+
+use crate::forecast::{DataPoint, Forecast};
+use crate::{local_time, Error, Result};
+
+/// Times without a format are shown like this.
+const DEFAULT_TIME_FORMAT: &str = "%H:%M";
+
+#[derive(Debug, PartialEq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Part {
+    Text(String),
+    Placeholder(Key, Option<String>),
+}
+
+#[derive(Debug, PartialEq)]
+enum Key {
+    Field(Scope, String),
+    Summary(Block),
+    Graph(Block, String),
+    Deg,
+    Icon,
+    Moon,
+    WindArrow,
+    PressureIcon,
+    Place,
+    Stale,
+    Alerts,
+}
+
+#[derive(Debug, PartialEq)]
+enum Scope {
+    Current,
+    Hourly(usize),
+    Daily(usize),
+}
+
+#[derive(Debug, PartialEq)]
+enum Block {
+    Hourly,
+    Daily,
+}
+
+/// The value of a data point's field.
+enum Value {
+    Number(Option<f64>),
+    Time(Option<i64>),
+    Text(Option<String>),
+}
+
+/// Draws a graph of some values of the named field, highlighting the one at the given position.
+pub type DrawGraph<'a> = &'a dyn Fn(&str, &[Option<f64>], Option<usize>) -> String;
+
+/// Everything a template can show, already formatted for the output mode where that matters.
+pub struct Context<'a> {
+    pub weather: &'a Forecast,
+    pub icon: String,
+    pub moon: String,
+    pub wind_arrow: String,
+    pub pressure_icon: String,
+    pub stale: String,
+    pub alerts: String,
+    pub graph: DrawGraph<'a>,
+    /// The position of the current hour in the hourly forecast.
+    pub position: Option<usize>,
+    /// Escapes text from the forecast, like the summary, that the output mode would otherwise
+    /// read as markup.
+    pub escape: fn(String) -> String,
+}
+
+impl Template {
+    pub fn render(&self, context: &Context<'_>) -> String {
+        let weather = context.weather;
+
+        self.parts
+            .iter()
+            .map(|part| {
+                let (key, spec) = match part {
+                    Part::Text(text) => return text.clone(),
+                    Part::Placeholder(key, spec) => (key, spec.as_ref().map(String::as_str)),
+                };
+
+                match key {
+                    Key::Field(scope, name) => {
+                        let point = match scope {
+                            Scope::Current => Some(&weather.current),
+                            Scope::Hourly(i) => weather.hourly.data.get(*i),
+                            Scope::Daily(i) => weather.daily.data.get(*i),
+                        };
+                        (context.escape)(
+                            point
+                                .and_then(|p| field(p, name))
+                                .map_or_else(String::new, |v| {
+                                    format_value(v, spec, weather.utc_offset)
+                                }),
+                        )
+                    }
+                    Key::Summary(Block::Hourly) => {
+                        (context.escape)(weather.hourly.summary.clone().unwrap_or_default())
+                    }
+                    Key::Summary(Block::Daily) => {
+                        (context.escape)(weather.daily.summary.clone().unwrap_or_default())
+                    }
+                    Key::Graph(block, name) => {
+                        let (data, position) = match block {
+                            Block::Hourly => (&weather.hourly.data, context.position),
+                            Block::Daily => (&weather.daily.data, None),
+                        };
+                        let values = data
+                            .iter()
+                            .map(|p| match field(p, name) {
+                                Some(Value::Number(n)) => n,
+                                _ => None,
+                            })
+                            .collect::<Vec<_>>();
+                        if values.is_empty() {
+                            String::new()
+                        } else {
+                            (context.graph)(name, &values, position)
+                        }
+                    }
+                    Key::Deg => "°".to_string(),
+                    Key::Icon => context.icon.clone(),
+                    Key::Moon => context.moon.clone(),
+                    Key::WindArrow => context.wind_arrow.clone(),
+                    Key::PressureIcon => context.pressure_icon.clone(),
+                    Key::Place => (context.escape)(weather.place.clone().unwrap_or_default()),
+                    Key::Stale => context.stale.clone(),
+                    Key::Alerts => context.alerts.clone(),
+                }
+            })
+            .collect()
+    }
+
+    /// Whether the template places the stale marker itself.
+    pub fn shows_stale(&self) -> bool {
+        self.parts.contains(&Part::Placeholder(Key::Stale, None))
+    }
+
+    /// Whether the template places the alerts marker itself.
+    pub fn shows_alerts(&self) -> bool {
+        self.parts.contains(&Part::Placeholder(Key::Alerts, None))
+    }
+}
+
+impl FromStr for Template {
+    type Err = Error;
+
+    fn from_str(template: &str) -> Result<Self> {
+        let error = |message: String| Error::Template(format!("{} in {:?}", message, template));
+
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.char_indices().peekable();
+
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '{' if chars.peek().map(|&(_, c)| c) == Some('{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek().map(|&(_, c)| c) == Some('}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '}' => return Err(error(format!("unmatched `}}` at {}", i))),
+                '{' => {
+                    let mut inner = String::new();
+                    loop {
+                        match chars.next() {
+                            Some((_, '}')) => break,
+                            Some((_, c)) => inner.push(c),
+                            None => return Err(error(format!("unclosed `{{` at {}", i))),
+                        }
+                    }
+
+                    if !text.is_empty() {
+                        parts.push(Part::Text(text.split_off(0)));
+                    }
+                    let (key, spec) = parse_placeholder(&inner).map_err(error)?;
+                    parts.push(Part::Placeholder(key, spec));
+                }
+                c => text.push(c),
+            }
+        }
+
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+
+        Ok(Self { parts })
+    }
+}
+
+fn parse_placeholder(placeholder: &str) -> std::result::Result<(Key, Option<String>), String> {
+    let (name, spec) = match placeholder.find(':') {
+        Some(i) => (&placeholder[..i], Some(placeholder[i + 1..].to_string())),
+        None => (placeholder, None),
+    };
+
+    let simple = match name {
+        "deg" => Some(Key::Deg),
+        "icon" => Some(Key::Icon),
+        "moon" => Some(Key::Moon),
+        "wind_arrow" => Some(Key::WindArrow),
+        "pressure_icon" => Some(Key::PressureIcon),
+        "place" => Some(Key::Place),
+        "stale" => Some(Key::Stale),
+        "alerts" => Some(Key::Alerts),
+        _ => None,
+    };
+    if let Some(key) = simple {
+        return match spec {
+            Some(_) => Err(format!("`{{{}}}` doesn't take a format", name)),
+            None => Ok((key, None)),
+        };
+    }
+
+    if name == "graph" {
+        let spec = spec.ok_or_else(|| {
+            "`{graph}` needs a field to draw, e.g. `{graph:pressure}`".to_string()
+        })?;
+        let (block, field_name) = match spec.split('.').collect::<Vec<_>>()[..] {
+            [name] | ["hourly", name] => (Block::Hourly, name),
+            ["daily", name] => (Block::Daily, name),
+            _ => return Err(format!("can't draw a graph of {:?}", spec)),
+        };
+        return match field(&DataPoint::default(), field_name) {
+            Some(Value::Number(_)) => Ok((Key::Graph(block, field_name.to_string()), None)),
+            Some(_) => Err(format!("can't draw a graph of {:?}", field_name)),
+            None => Err(format!("unknown field {:?}", field_name)),
+        };
+    }
+
+    let segments = name.split('.').collect::<Vec<_>>();
+    let (scope, field_name) = match segments[..] {
+        ["hourly", "summary"] => return without_spec(Key::Summary(Block::Hourly), name, spec),
+        ["daily", "summary"] => return without_spec(Key::Summary(Block::Daily), name, spec),
+        [field_name] | ["current", field_name] => (Scope::Current, field_name),
+        ["today", field_name] => (Scope::Daily(0), field_name),
+        ["hourly", i, field_name] => (Scope::Hourly(parse_index(i)?), field_name),
+        ["daily", i, field_name] => (Scope::Daily(parse_index(i)?), field_name),
+        _ => return Err(format!("unknown placeholder `{{{}}}`", name)),
+    };
+
+    let field_name = match field_name {
+        "temp" => "temperature",
+        "feels_like" => "apparent_temperature",
+        name => name,
+    };
+
+    match (field(&DataPoint::default(), field_name), &spec) {
+        (None, _) => Err(format!("unknown placeholder `{{{}}}`", name)),
+        (Some(_), None) => Ok((Key::Field(scope, field_name.to_string()), spec)),
+        (Some(Value::Number(_)), Some(s)) => {
+            if s.starts_with('.') && s[1..].parse::<usize>().is_ok() {
+                Ok((Key::Field(scope, field_name.to_string()), spec))
+            } else {
+                Err(format!(
+                    "`{{{}}}` takes a precision like `.1`, not {:?}",
+                    name, s
+                ))
+            }
+        }
+        (Some(Value::Time(_)), Some(s)) => {
+            if StrftimeItems::new(s).any(|i| i == Item::Error) {
+                Err(format!("invalid time format {:?} for `{{{}}}`", s, name))
+            } else {
+                Ok((Key::Field(scope, field_name.to_string()), spec))
+            }
+        }
+        (Some(Value::Text(_)), Some(_)) => Err(format!("`{{{}}}` doesn't take a format", name)),
+    }
+}
+
+fn without_spec(
+    key: Key,
+    name: &str,
+    spec: Option<String>,
+) -> std::result::Result<(Key, Option<String>), String> {
+    match spec {
+        Some(_) => Err(format!("`{{{}}}` doesn't take a format", name)),
+        None => Ok((key, None)),
+    }
+}
+
+fn parse_index(i: &str) -> std::result::Result<usize, String> {
+    i.parse()
+        .map_err(|_| format!("expected a number instead of {:?}", i))
+}
+
+/// Look up a field of a data point by name, or `None` if there isn't one by that name.
+fn field(p: &DataPoint, name: &str) -> Option<Value> {
+    use self::Value::*;
+
+    Some(match name {
+        "time" => Time(Some(p.time)),
+        "summary" => Text(p.summary.clone()),
+        "icon" => Text(p.icon.map(|i| i.name().to_string())),
+        "temperature" => Number(p.temperature),
+        "apparent_temperature" => Number(p.apparent_temperature),
+        "temperature_high" => Number(p.temperature_high),
+        "temperature_high_time" => Time(p.temperature_high_time),
+        "temperature_low" => Number(p.temperature_low),
+        "temperature_low_time" => Time(p.temperature_low_time),
+        "dew_point" => Number(p.dew_point),
+        "humidity" => Number(p.humidity),
+        "pressure" => Number(p.pressure),
+        "wind_speed" => Number(p.wind_speed),
+        "wind_gust" => Number(p.wind_gust),
+        "wind_bearing" => Number(p.wind_bearing),
+        "cloud_cover" => Number(p.cloud_cover),
+        "uv_index" => Number(p.uv_index),
+        "visibility" => Number(p.visibility),
+        "precip_intensity" => Number(p.precip_intensity),
+        "precip_probability" => Number(p.precip_probability),
+        "precip_accumulation" => Number(p.precip_accumulation),
+        "precip_type" => Text(p.precip_type.map(|t| t.name().to_string())),
+        "sunrise_time" => Time(p.sunrise_time),
+        "sunset_time" => Time(p.sunset_time),
+        "moon_phase" => Number(p.moon_phase),
+        _ => return None,
+    })
+}
+
+/// Format a field's value, with times in the location's timezone.
+fn format_value(value: Value, spec: Option<&str>, offset: Option<i32>) -> String {
+    match value {
+        Value::Number(Some(n)) => match spec.and_then(|s| s[1..].parse::<usize>().ok()) {
+            Some(precision) => format!("{:.*}", precision, n),
+            None => n.to_string(),
+        },
+        Value::Time(Some(t)) => local_time(t, offset)
+            .format(spec.unwrap_or(DEFAULT_TIME_FORMAT))
+            .to_string(),
+        Value::Text(Some(text)) => text,
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forecast::{DataBlock, Icon};
+
+    fn render(template: &str) -> String {
+        let weather = Forecast {
+            place: Some("Winnipeg".to_string()),
+            utc_offset: Some(-6 * 60 * 60),
+            current: DataPoint {
+                // 2019-06-01T17:00:00Z
+                time: 1_559_408_400,
+                temperature: Some(21.6),
+                apparent_temperature: Some(23.2),
+                summary: Some("Clear".to_string()),
+                icon: Some(Icon::ClearDay),
+                ..DataPoint::default()
+            },
+            hourly: DataBlock {
+                summary: Some("Clear all day.".to_string()),
+                data: vec![
+                    DataPoint {
+                        pressure: Some(1010.),
+                        ..DataPoint::default()
+                    },
+                    DataPoint {
+                        pressure: Some(1012.),
+                        ..DataPoint::default()
+                    },
+                ],
+            },
+            ..Forecast::default()
+        };
+        let graph = |name: &str, values: &[Option<f64>], position: Option<usize>| {
+            format!("<{} {} values at {:?}>", values.len(), name, position)
+        };
+
+        let context = Context {
+            weather: &weather,
+            icon: "I".to_string(),
+            moon: "M".to_string(),
+            wind_arrow: "↑".to_string(),
+            pressure_icon: "P".to_string(),
+            stale: "(old)".to_string(),
+            alerts: "!1".to_string(),
+            graph: &graph,
+            position: Some(1),
+            escape: |text| text,
+        };
+        template.parse::<Template>().unwrap().render(&context)
+    }
+
+    fn error(template: &str) -> String {
+        match template.parse::<Template>() {
+            Err(Error::Template(message)) => message,
+            r => panic!("expected a template error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn placeholders() {
+        assert_eq!(
+            "I 22° Clear (23.2°) {braces}",
+            render("{icon} {temp:.0}{deg} {summary} ({feels_like}{deg}) {{braces}}")
+        );
+        assert_eq!(
+            "Winnipeg: P <2 pressure values at Some(1)> M ↑ (old)",
+            render("{place}: {pressure_icon} {graph:pressure} {moon} {wind_arrow} {stale}")
+        );
+        assert_eq!("Clear !1", render("{summary} {alerts}"));
+        assert_eq!(
+            "1012.0 Clear all day. clear-day",
+            render("{hourly.1.pressure:.1} {hourly.summary} {current.icon}")
+        );
+        assert_eq!(
+            "[]",
+            render("[{daily.3.temperature_high}{today.sunrise_time}]")
+        );
+        assert_eq!("11:00", render("{time:%H:%M}"));
+    }
+
+    #[test]
+    fn bad_templates() {
+        assert_eq!(
+            "unknown placeholder `{temperture}` in \"{temperture}°\"",
+            error("{temperture}°")
+        );
+        assert_eq!("unclosed `{` at 5 in \"temp {temp\"", error("temp {temp"));
+        assert_eq!("unmatched `}` at 4 in \"temp}\"", error("temp}"));
+        assert!(error("{temp:%H}").contains("takes a precision"));
+        assert!(error("{time:%Q}").contains("invalid time format"));
+        assert!(error("{graph}").contains("needs a field"));
+        assert!(error("{graph:summary}").contains("can't draw a graph"));
+        assert!(error("{hourly.next.temperature}").contains("expected a number"));
+        assert!(error("{icon:.1}").contains("doesn't take a format"));
+    }
+
+    #[test]
+    fn stale_placeholder() {
+        assert!("{temp} {stale}".parse::<Template>().unwrap().shows_stale());
+        assert!(!"{temp}".parse::<Template>().unwrap().shows_stale());
+        assert!("{alerts} {temp}"
+            .parse::<Template>()
+            .unwrap()
+            .shows_alerts());
+        assert!(!"{temp}".parse::<Template>().unwrap().shows_alerts());
+    }
+}
@@ -0,0 +1,120 @@
+//! A tiny HTTP server for serving recorded responses to providers under test, and the recorded
+//! forecast that the rest of the tests share.
+
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::forecast::Forecast;
+use crate::provider::{DarkSky, Provider};
+
+pub struct Route {
+    /// Requests whose path starts with this are answered by this route.
+    pub path: &'static str,
+    pub status: u16,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: String,
+}
+
+impl Route {
+    pub fn new(path: &'static str, body: &str) -> Self {
+        Self {
+            path,
+            status: 200,
+            headers: vec![("Content-Type", "application/json".to_string())],
+            body: body.to_string(),
+        }
+    }
+}
+
+pub struct TestServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl TestServer {
+    /// Start serving `routes` on a random local port. Unmatched paths get a 404.
+    ///
+    /// `{{url}}` in a response body is replaced with the server's URL, for responses that link
+    /// to other resources.
+    pub fn new(mut routes: Vec<Route>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("couldn't bind test server");
+        let url = format!(
+            "http://{}",
+            listener
+                .local_addr()
+                .expect("couldn't get test server address")
+        );
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        for route in &mut routes {
+            route.body = route.body.replace("{{url}}", &url);
+        }
+
+        let seen = Arc::clone(&requests);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = match stream {
+                    Ok(s) => s,
+                    Err(_) => continue,
+                };
+
+                let mut request = String::new();
+                let mut reader = BufReader::new(&stream);
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap_or(0) == 0 || line == "\r\n" {
+                        break;
+                    }
+                    request.push_str(&line);
+                }
+
+                let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+                seen.lock()
+                    .expect("test server lock poisoned")
+                    .push(request);
+
+                let response = match routes.iter().find(|r| path.starts_with(r.path)) {
+                    Some(route) => {
+                        let headers = route
+                            .headers
+                            .iter()
+                            .map(|(k, v)| format!("{}: {}\r\n", k, v))
+                            .collect::<String>();
+                        format!(
+                            "HTTP/1.1 {} X\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                            route.status,
+                            headers,
+                            route.body.len(),
+                            route.body
+                        )
+                    }
+                    None => {
+                        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                            .to_string()
+                    }
+                };
+
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    /// The request line and headers of every request received so far.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests
+            .lock()
+            .expect("test server lock poisoned")
+            .clone()
+    }
+}
+
+/// The forecast in `tests/fixtures/darksky.json`.
+pub fn darksky_forecast() -> Forecast {
+    let raw = serde_json::from_str(include_str!("../tests/fixtures/darksky.json")).unwrap();
+    DarkSky::new(None, "").parse(raw).unwrap()
+}